@@ -0,0 +1,399 @@
+use std::{env, path::Path, process};
+
+use crate::{datas::{enums, errors::CacheError}, envfuncs};
+
+/// Number of hex characters of a file hash that are put into the cache key.
+const KEY_HASH_LENGTH: usize = 16;
+
+/// [`get_cache_key`] renders the cache key of the current job.
+/// The key is built from the `CACHE_KEY` template if it is set. Otherwise it defaults to `{os}-{branch}`,
+/// followed by `-{files}` if `CACHE_KEY_FILES` is set.
+///
+/// Returns:
+///
+/// A [`String`] representing the cache key. Exits the program if the key could not be rendered.
+pub fn get_cache_key() -> String {
+    return get_cache_key_for_branch(envfuncs::get_branch_name().as_str());
+}
+
+/// [`get_cache_key_for_branch`] renders the cache key of the current job as if it was running on `branch`.
+///
+/// Arguments:
+///
+/// * `branch`: type of [`&str`], the branch name that is inserted for `{branch}`.
+///
+/// Returns:
+///
+/// A [`String`] representing the cache key. Exits the program if the key could not be rendered.
+pub fn get_cache_key_for_branch(branch: &str) -> String {
+    let render_result = render_key(get_key_template().as_str(), branch);
+
+    if render_result.is_err(){
+        let render_error = render_result.unwrap_err();
+        eprintln!("Could not create the cache key. Error was: {}", render_error);
+        process::exit(render_error.exit_code());
+    }
+
+    return render_result.unwrap();
+}
+
+/// [`get_restore_keys`] returns the ordered list of keys that are tried when downloading the cache.
+/// If `CACHE_RESTORE_KEYS` is set it contains a list of key templates seperated by `,` (see [`split_templates`]). Templates ending
+/// with a `*` match any cache whose key starts with the rendered template.
+/// Otherwise the key of the current branch is tried first, followed by the keys of `CI_MERGE_REQUEST_TARGET_BRANCH_NAME` and
+/// `CI_DEFAULT_BRANCH`. After that any cache of these branches whose key only differs in the file hashes is accepted
+/// (see [`render_hash_pattern`]), which matches caches with outdated file hashes but not the caches of other branches.
+///
+/// Returns:
+///
+/// A [`Vec<enums::RestoreKey>`] without duplicates. Exits the program if a key could not be rendered.
+pub fn get_restore_keys() -> Vec<enums::RestoreKey> {
+    let mut restore_keys: Vec<enums::RestoreKey> = Vec::new();
+    let branch = envfuncs::get_branch_name();
+    let restore_key_templates = env::var("CACHE_RESTORE_KEYS").unwrap_or_default();
+
+    if !restore_key_templates.trim().is_empty(){
+        for template in split_templates(restore_key_templates.as_str()){
+            let (template, is_prefix) = match template.strip_suffix('*') {
+                Some(prefix_template) => (prefix_template, true),
+                None => (template, false)
+            };
+            let render_result = render_key(template, branch.as_str());
+
+            if render_result.is_err(){
+                let render_error = render_result.unwrap_err();
+                eprintln!("Could not create the restore key {}. Error was: {}", template, render_error);
+                process::exit(render_error.exit_code());
+            }
+
+            if is_prefix {
+                restore_keys.push(enums::RestoreKey::Prefix(render_result.unwrap()));
+            }else{
+                restore_keys.push(enums::RestoreKey::Exact(render_result.unwrap()));
+            }
+        }
+    }else{
+        let mut branches = vec![branch];
+
+        for fallback_branch_var in ["CI_MERGE_REQUEST_TARGET_BRANCH_NAME", "CI_DEFAULT_BRANCH"]{
+            let fallback_branch = env::var(fallback_branch_var).unwrap_or_default();
+
+            if !fallback_branch.trim().is_empty(){
+                branches.push(fallback_branch);
+            }
+        }
+
+        for fallback_branch in &branches{
+            restore_keys.push(enums::RestoreKey::Exact(get_cache_key_for_branch(fallback_branch)));
+        }
+
+        for fallback_branch in &branches{
+            let pattern_result = render_hash_pattern(get_key_template().as_str(), fallback_branch);
+
+            if pattern_result.is_err(){
+                let render_error = pattern_result.unwrap_err();
+                eprintln!("Could not create the restore key of the branch {}. Error was: {}", fallback_branch, render_error);
+                process::exit(render_error.exit_code());
+            }
+
+            // Keys without file hashes have no outdated versions, those are already covered by the exact key.
+            let pattern = pattern_result.unwrap();
+
+            if pattern.contains('*'){
+                restore_keys.push(enums::RestoreKey::AnyHash(pattern));
+            }
+        }
+    }
+
+    let mut unique_keys: Vec<enums::RestoreKey> = Vec::new();
+
+    for restore_key in restore_keys{
+        if !unique_keys.contains(&restore_key){
+            unique_keys.push(restore_key);
+        }
+    }
+
+    return unique_keys;
+}
+
+/// [`get_key_template`] returns the template the cache key is rendered from.
+///
+/// Returns:
+///
+/// The value of `CACHE_KEY` or the default template if it is not set.
+pub fn get_key_template() -> String {
+    let template = env::var("CACHE_KEY").unwrap_or_default();
+
+    if !template.trim().is_empty(){
+        return template;
+    }
+
+    if get_key_files().is_empty(){
+        return String::from("{os}-{branch}");
+    }
+
+    return String::from("{os}-{branch}-{files}");
+}
+
+/// [`get_key_files`] returns the files listed in the `CACHE_KEY_FILES` enviorement variable.
+/// Multiple files are seperated by a `,` or `;`.
+///
+/// Returns:
+///
+/// A [`Vec<String>`] of file paths. Empty if the variable is not set.
+pub fn get_key_files() -> Vec<String> {
+    return split_file_list(env::var("CACHE_KEY_FILES").unwrap_or_default().as_str());
+}
+
+/// [`render_key`] renders a cache key template. The template consists of literal text and the following placeholders:
+///
+/// * `{os}`: the operating system type (e.g. `Unix`).
+/// * `{branch}`: the branch name reached in via `branch`.
+/// * `{env:NAME}`: the value of the enviorement variable `NAME` (e.g. `{env:CI_JOB_NAME}`).
+/// * `{hash:FILE,FILE}`: a hash over the contents of the listed files (e.g. `{hash:Cargo.lock}`).
+/// * `{files}`: a hash over the contents of the files listed in `CACHE_KEY_FILES`.
+///
+/// Characters that are not allowed in file names on the storage backends are replaced with `_`.
+///
+/// Arguments:
+///
+/// * `template`: type of [`&str`], the template to render.
+/// * `branch`: type of [`&str`], the branch name that is inserted for `{branch}`.
+///
+/// Returns:
+///
+/// The rendered key as a [`String`] or an error if the template is invalid or a file could not be hashed.
+pub fn render_key(template: &str, branch: &str) -> Result<String, CacheError> {
+    return render_template(template, branch, false);
+}
+
+/// [`render_hash_pattern`] renders a cache key template like [`render_key`], but replaces the `{hash:...}` and `{files}` placeholders
+/// with a `*` for every character of the hash. The pattern matches every key the template rendered to for `branch`,
+/// no matter which contents the hashed files had (see [`enums::RestoreKey::AnyHash`]).
+///
+/// Arguments:
+///
+/// * `template`: type of [`&str`], the template to render.
+/// * `branch`: type of [`&str`], the branch name that is inserted for `{branch}`.
+///
+/// Returns:
+///
+/// The pattern as a [`String`] or an error if the template is invalid. The pattern contains no `*` if the template has no hashes.
+pub fn render_hash_pattern(template: &str, branch: &str) -> Result<String, CacheError> {
+    return render_template(template, branch, true);
+}
+
+/// [`render_template`] renders a cache key template, see [`render_key`] and [`render_hash_pattern`].
+/// Literal text and placeholder values are sanitized seperately, so the `*` of hash patterns is never replaced.
+fn render_template(template: &str, branch: &str, hashes_as_pattern: bool) -> Result<String, CacheError> {
+    let mut key = String::new();
+    let mut raw_key = String::new();
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        key.push_str(sanitize_key(&rest[..open]).as_str());
+        raw_key.push_str(&rest[..open]);
+        let close = rest[open..].find('}');
+
+        if close.is_none(){
+            return Err(CacheError::config("The cache key template contains a { without a matching }."));
+        }
+
+        let close = open + close.unwrap();
+        let placeholder = &rest[open + 1..close];
+        rest = &rest[close + 1..];
+
+        if hashes_as_pattern && (placeholder == "files" || placeholder.starts_with("hash:")){
+            key.push_str("*".repeat(KEY_HASH_LENGTH).as_str());
+            raw_key.push('*');
+            continue;
+        }
+
+        let value = render_placeholder(placeholder, branch);
+
+        if value.is_err(){
+            return value;
+        }
+
+        let value = value.unwrap();
+        key.push_str(sanitize_key(value.as_str()).as_str());
+        raw_key.push_str(value.as_str());
+    }
+
+    key.push_str(sanitize_key(rest).as_str());
+    raw_key.push_str(rest);
+
+    if raw_key.trim().is_empty(){
+        return Err(CacheError::config("The cache key template rendered to an empty key."));
+    }
+
+    return Ok(key);
+}
+
+/// [`split_templates`] splits a list of key templates seperated by `,`. Commas inside of a placeholder
+/// (e.g. `{hash:Cargo.lock,package-lock.json}`) belong to the placeholder and don't seperate templates.
+///
+/// Arguments:
+///
+/// * `templates`: type of [`&str`], the list of templates.
+///
+/// Returns:
+///
+/// A [`Vec<&str>`] of the trimmed templates without empty entries.
+fn split_templates(templates: &str) -> Vec<&str> {
+    let mut split_templates: Vec<&str> = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (position, character) in templates.char_indices() {
+        match character {
+            '{' => { depth += 1; }
+            '}' => { depth = if depth > 0 { depth - 1 } else { 0 }; }
+            ',' if depth == 0 => {
+                split_templates.push(&templates[start..position]);
+                start = position + 1;
+            }
+            _ => {}
+        }
+    }
+
+    split_templates.push(&templates[start..]);
+    return split_templates.into_iter().map(|template| template.trim()).filter(|template| !template.is_empty()).collect();
+}
+
+/// [`render_placeholder`] renders a single placeholder of a cache key template. See [`render_key`] for the supported placeholders.
+fn render_placeholder(placeholder: &str, branch: &str) -> Result<String, CacheError> {
+    match placeholder.split_once(':') {
+        None if placeholder == "os" => { return Ok(enums::OsType::get_ostype().to_string()); }
+        None if placeholder == "branch" => { return Ok(branch.to_string()); }
+        None if placeholder == "files" => {
+            let key_files = get_key_files();
+
+            if key_files.is_empty(){
+                return Err(CacheError::config("The cache key template uses {files} but CACHE_KEY_FILES is not set."));
+            }
+
+            return hash_files(&key_files);
+        }
+        Some(("env", name)) => {
+            let value = env::var(name.trim());
+
+            if value.is_err(){
+                return Err(CacheError::config("The cache key template references an enviorement variable that is not set."));
+            }
+
+            return Ok(value.unwrap());
+        }
+        Some(("hash", files)) => { return hash_files(&split_file_list(files)); }
+        _ => { return Err(CacheError::config("The cache key template contains an unknown placeholder. Supported are {os}, {branch}, {files}, {env:NAME} and {hash:FILE}.")); }
+    }
+}
+
+/// [`hash_files`] creates a short hash over the contents of multiple files.
+/// The hash of each file is combined with it's path so renaming a file invalidates the key as well.
+///
+/// Arguments:
+///
+/// * `files`: type of [`&[String]`], the paths of the files to hash.
+///
+/// Returns:
+///
+/// The first characters of the combined sha256 hash or an error if a file could not be read.
+fn hash_files(files: &[String]) -> Result<String, CacheError> {
+    let mut combined = String::new();
+
+    for file in files{
+        let file_hash = sha256::try_digest(Path::new(file));
+
+        if file_hash.is_err(){
+            eprintln!("Could not hash the cache key file: {}", file);
+            return Err(CacheError::io("Could not read a file that is part of the cache key. Please make sure all files listed exist.", file_hash.unwrap_err()));
+        }
+
+        combined.push_str(format!("{}:{}\n", file, file_hash.unwrap()).as_str());
+    }
+
+    let mut hash = sha256::digest(combined);
+    hash.truncate(KEY_HASH_LENGTH);
+
+    return Ok(hash);
+}
+
+/// Splits a list of files seperated by `,` or `;` and removes empty entries.
+fn split_file_list(files: &str) -> Vec<String> {
+    return files.split([',', ';'])
+        .map(|file| file.trim().to_string())
+        .filter(|file| !file.is_empty())
+        .collect();
+}
+
+/// Replaces characters that may not be used in object names (like the `/` of `feature/xyz` branches) or that have a meaning in urls
+/// (`#`, `%`) with `_`.
+fn sanitize_key(key: &str) -> String {
+    return key.chars()
+        .map(|character| match character {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '%' => '_',
+            character if character.is_whitespace() || character.is_control() => '_',
+            character => character
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::TestEnv;
+
+    #[test]
+    fn split_templates_keeps_commas_of_placeholders() {
+        assert_eq!(split_templates("{os}-{hash:Cargo.lock,package-lock.json}, {os}-main ,,{os}-*"),
+            vec!["{os}-{hash:Cargo.lock,package-lock.json}", "{os}-main", "{os}-*"]);
+    }
+
+    #[test]
+    fn restore_keys_with_hash_placeholders() {
+        let test_env = TestEnv::new();
+        test_env.write("a.lock", "a");
+        test_env.write("b.lock", "b");
+        test_env.set("CACHE_RESTORE_KEYS", "{branch}-{hash:a.lock,b.lock},{branch}-*");
+
+        let restore_keys = get_restore_keys();
+
+        assert_eq!(restore_keys.len(), 2);
+        assert_eq!(restore_keys[0], enums::RestoreKey::Exact(render_key("{branch}-{hash:a.lock;b.lock}", "main").unwrap()));
+        assert_eq!(restore_keys[1], enums::RestoreKey::Prefix(String::from("main-")));
+    }
+
+    #[test]
+    fn branch_fallback_only_matches_outdated_hashes_of_the_branch() {
+        let test_env = TestEnv::new();
+        test_env.write("Cargo.lock", "v2");
+        test_env.set("CACHE_KEY_FILES", "Cargo.lock");
+        let os = enums::OsType::get_ostype().to_string();
+
+        let restore_keys = get_restore_keys();
+        let fallback = restore_keys.last().unwrap();
+
+        assert_eq!(fallback, &enums::RestoreKey::AnyHash(format!("{}-main-{}", os, "*".repeat(KEY_HASH_LENGTH))));
+        assert!(fallback.matches(format!("{}-main-0123456789abcdef", os).as_str()));
+        assert!(!fallback.matches(format!("{}-main-old-0123456789ab", os).as_str()));
+        assert!(!fallback.matches(format!("{}-main-old-0123456789abcdef", os).as_str()));
+        assert!(!fallback.matches(format!("{}-main-0123456789abcdeg", os).as_str()));
+    }
+
+    #[test]
+    fn branch_fallback_is_skipped_without_hashes() {
+        let _test_env = TestEnv::new();
+
+        let restore_keys = get_restore_keys();
+
+        assert_eq!(restore_keys, vec![enums::RestoreKey::Exact(format!("{}-main", enums::OsType::get_ostype().to_string()))]);
+    }
+
+    #[test]
+    fn hash_pattern_sanitizes_values() {
+        let _test_env = TestEnv::new();
+
+        assert_eq!(render_hash_pattern("{branch}:{files}", "feature/a b#1%").unwrap(), format!("feature_a_b_1__{}", "*".repeat(KEY_HASH_LENGTH)));
+    }
+}
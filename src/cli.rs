@@ -0,0 +1,560 @@
+use std::{env, io};
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+
+use crate::datas::{enums, structs};
+
+/// Documentation of the enviorement variables, shown at the end of `--help`.
+const ENV_HELP: &str = "\
+Enviorement variables:
+Caching is usually done through specifying enviorement variables. Flags of the commands override the matching variables.
+All settings can also be put into a config file, see the Config file section below.
+
+Storage backends:
+  CACHE_BACKEND         The storage backend to use. Either webdav (default), ftp, ftps, s3 or fs
+  WEBDAVADDR            The web address base to use to store Data (e.g.: https://example.com). Required for the webdav backend
+  WEBDAVUSER            The user to use for Webdav authentication. Required for the webdav backend
+  WEBDAVPASS            The password to use for Webdav authentication. Required for the webdav backend
+  FTPADDR               The ftp server to store Data on (e.g.: example.com or example.com:2121). Required for the ftp backend
+  FTPUSER               The user to use for ftp authentication. Required for the ftp backend
+  FTPPASS               The password to use for ftp authentication. Required for the ftp backend
+  FTPS                  Set to true to secure the ftp connection via explicit TLS. Optional
+  S3ENDPOINT            The base url of the S3 compatible server (e.g.: https://minio.example.com:9000). Required for the s3 backend
+  S3BUCKET              The bucket to store Data in. Required for the s3 backend
+  S3ACCESSKEY           The access key to use for S3 authentication. Required for the s3 backend
+  S3SECRETKEY           The secret key to use for S3 authentication. Required for the s3 backend
+  S3PREFIX              A prefix all cache objects are stored under inside of the bucket. Optional
+  S3REGION              The region used to sign requests. Optional, defaults to us-east-1
+  FSROOT                The directory to store Data in (e.g.: the mount point of a NFS share). Required for the fs backend
+  CI_PROJECT_ID         Usually a default value set by gitlab itself
+  CI_COMMIT_BRANCH      Usually a default value set by gitlab itself
+Credentials can not be passed as flags. I recommend storing them in a secured variable on the gitlab server.
+
+Cache keys:
+  CACHE_KEY             A template for the key, defaults to {os}-{branch}. Literal text can be combined with these placeholders:
+                          {os}          The operating system type (Windows or Unix)
+                          {branch}      The value of CI_COMMIT_BRANCH
+                          {env:NAME}    The value of the enviorement variable NAME (e.g.: {env:CI_JOB_NAME})
+                          {hash:FILE}   A hash over the contents of the listed files, seperated by , (e.g.: {hash:Cargo.lock})
+                          {files}       A hash over the contents of the files listed in CACHE_KEY_FILES
+  CACHE_KEY_FILES       Files whose contents are hashed into the key, seperated by , (e.g.: Cargo.lock,package-lock.json).
+                        If it is set and CACHE_KEY is not, the key defaults to {os}-{branch}-{files}
+  CACHE_RESTORE_KEYS    Keys that are tried in order when downloading, seperated by , (e.g.: {os}-{branch},{os}-main,{os}-*).
+                        Commas inside of placeholders like {hash:a,b} don't seperate keys.
+                        Keys ending with * match any cache starting with the key. If not set the keys of CI_COMMIT_BRANCH,
+                        CI_MERGE_REQUEST_TARGET_BRANCH_NAME and CI_DEFAULT_BRANCH are tried, followed by any cache of these
+                        branches whose key only differs in the file hashes. The key that was restored is written to the .cache_key file.
+
+Download status:
+  CACHE_DOTENV          The file the status of a download is written to, defaults to cache.env. The file uses the dotenv format,
+                        so it can be passed to later jobs via artifacts:reports:dotenv. It contains:
+                          CACHE_HIT           true if the cache of the exact key (CACHE_KEY) was restored, false otherwise
+                          CACHE_KEY_MATCHED   The key of the restored cache. Set to a fallback key if CACHE_HIT is false, empty on a miss
+                          CACHE_SIZE          The size of the downloaded archive in bytes, 0 on a miss
+  CACHE_FAIL_ON_MISS    Set to true to exit with code 3 if no cache was found, instead of 0
+
+Specifing Cache:
+  cachepath_<VARNAME>   A folder to cache, where <VARNAME> is a unique name (e.g.: cachepath_homedir=/home/myawsomeuser)
+  cachefile_<VARNAME>   A file to cache, where <VARNAME> is a unique name
+                        Both may contain glob patterns (e.g.: cachepath_nm=**/node_modules, cachepath_deps=target/*/deps).
+                        * does not match /, use ** to match any number of folders. Relative patterns start in the current folder.
+                        Restoring finds the cached objects by <VARNAME>, so use the same name when saving and restoring.
+                        Objects inside of CI_PROJECT_DIR or the home directory are restored relative to it, even if the project
+                        is checked out elsewhere or the home directory differs between jobs.
+  cacheexclude_<VARNAME> A glob pattern of files and folders that are left out of the cache (e.g.: cacheexclude_inc=target/**/incremental).
+                        Patterns are matched against the absolute path, the path relative to the current folder and the path
+                        inside of the cache.
+  CACHE_RESTORE_ROOTS   Additional directories the cache may be restored into, seperated like PATH. By default we only restore
+                        into the project directory (CI_PROJECT_DIR) and the home directory. Entries outside of these,
+                        entries containing .. and entries with symlinks pointing outside of them are refused.
+  CACHE_CROSS_OS        Set to true to restore caches created on another operating system. Needs a CACHE_KEY without {os},
+                        so jobs of both operating systems use the same archive. Best used for OS independent content
+                        (e.g.: downloaded dependency tarballs).
+  CACHE_PATH_REMAP      Pairs of absolute paths mapped between operating systems, seperated by , (e.g.: C:\\Users\\ci=/home/ci).
+                        Paths are stored with / in the cache, locations inside of CI_PROJECT_DIR or the home directory
+                        don't need to be mapped.
+  CACHE_COMPRESSION     The compression of zip archives. Either stored, deflate (default), bzip2 or zstd, optionally followed
+                        by a level (e.g.: zstd:3). The compression is stored in the archive, so restoring works the same for all of them.
+  CACHE_ARCHIVE_FORMAT  The format of the archive. Either zip (default), tar.zst or tar.gz. The format is detected automatically
+                        when downloading. All formats keep symlinks, permissions and modification times.
+                        Zip archives round modification times to two seconds.
+  CACHE_FORCE_UPLOAD    Set to true to upload the cache even if it's contents did not change. By default a digest over the
+                        cached files (paths, permissions and contents, not modification times) is stored next to the archive
+                        and the upload is skipped if the archive on the storage backend already has the same digest.
+
+Config file:
+  The config file (worker-cache.toml, worker-cache.yaml or the file passed via --config / CACHE_CONFIG) contains the same
+  settings as the enviorement variables. Settings are used in the order: flags > enviorement variables > config file > defaults.
+
+    backend = \"webdav\"                           # CACHE_BACKEND
+    [webdav]                                      # also [ftp] (address, user, password, secure), [s3] and [fs] (root)
+    address = \"https://example.com\"              # WEBDAVADDR
+    user = \"ci\"                                  # WEBDAVUSER
+    password = { env = \"MY_MASKED_VARIABLE\" }    # WEBDAVPASS, credentials may also be read via { file = \"/run/secrets/pass\" }
+    [s3]                                          # endpoint, bucket, prefix, region, access_key, secret_key
+    [key]
+    template = \"{os}-{branch}-{files}\"           # CACHE_KEY
+    files = [\"Cargo.lock\"]                       # CACHE_KEY_FILES
+    restore = [\"{os}-{branch}\", \"{os}-*\"]        # CACHE_RESTORE_KEYS
+    [archive]
+    format = \"tar.zst\"                           # CACHE_ARCHIVE_FORMAT
+    compression = \"zstd:3\"                       # CACHE_COMPRESSION
+    force = false                                 # CACHE_FORCE_UPLOAD
+    [restore]
+    roots = [\"/opt/toolcache\"]                   # CACHE_RESTORE_ROOTS
+    cross_os = false                              # CACHE_CROSS_OS
+    remap = [\"C:/tools=/opt/tools\"]              # CACHE_PATH_REMAP
+    [download]
+    dotenv = \"cache.env\"                        # CACHE_DOTENV
+    fail_on_miss = true                           # CACHE_FAIL_ON_MISS
+    [paths]
+    cargo = \"/home/myawsomeuser/.cargo\"          # cachepath_cargo
+    [files]
+    lockfile = \"Cargo.lock\"                      # cachefile_lockfile
+    [excludes]
+    incremental = \"target/**/incremental\"        # cacheexclude_incremental
+
+Exit codes:
+  0  Success. A download that finds no matching cache exits with 0 as well, unless --fail-on-miss is passed
+  1  Unsupported operating system
+  2  Invalid arguments, enviorement variables or config file
+  3  The cache does not exist on the storage backend, or no cache was found with --fail-on-miss
+     (archives that don't match the sha256 checksum stored in them are skipped like missing caches)
+  4  Error while accessing the local file system
+  5  Error while communicating with the storage backend (the http status or ftp response is printed)
+  6  Error while creating or extracting the archive
+  7  The data.json file of the cache could not be written or read
+  8  The cache contents are invalid or were refused (e.g. entries outside of the restore roots)";
+
+/// Caches directories and files of gitlab jobs on a storage backend and restores them in later jobs.
+#[derive(Parser, Debug)]
+#[command(name = "worker-cache", version, after_long_help = ENV_HELP)]
+pub struct Cli {
+    /// The storage backend to use (webdav, ftp, ftps, s3 or fs). Overrides CACHE_BACKEND
+    #[arg(long, global = true, value_parser = parse_backend)]
+    pub backend: Option<String>,
+
+    /// The id of the project the cache belongs to. Overrides CI_PROJECT_ID
+    #[arg(long, global = true)]
+    pub project_id: Option<String>,
+
+    /// The branch the cache belongs to. Overrides CI_COMMIT_BRANCH
+    #[arg(long, global = true)]
+    pub branch: Option<String>,
+
+    /// The config file to use. Overrides CACHE_CONFIG, defaults to worker-cache.toml or worker-cache.yaml if they exist
+    #[arg(long, global = true)]
+    pub config: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Command
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Archives all cached files and folders and uploads the archive to the storage backend
+    #[command(visible_alias = "backup")]
+    Save {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        #[command(flatten)]
+        paths: PathArgs,
+
+        /// A glob pattern of files and folders to leave out of the cache, may be passed multiple times. Works like a cacheexclude_ enviorement variable
+        #[arg(long = "exclude", value_name = "PATTERN")]
+        excludes: Vec<String>,
+
+        /// The compression of zip archives (e.g.: zstd:3). Overrides CACHE_COMPRESSION
+        #[arg(long, value_parser = parse_compression)]
+        compression: Option<String>,
+
+        /// Upload the cache even if it's contents did not change. Overrides CACHE_FORCE_UPLOAD
+        #[arg(long)]
+        force: bool
+    },
+    /// Downloads the archive and extracts it into the .cache folder. With --restore the files are restored straight from the archive instead
+    Download {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// Keys that are tried in order, seperated by , (e.g.: {os}-{branch},{os}-*). Overrides CACHE_RESTORE_KEYS
+        #[arg(long)]
+        restore_keys: Option<String>,
+
+        /// Restores the files straight from the archive to their locations without extracting them into the .cache folder
+        #[arg(long)]
+        restore: bool,
+
+        /// The dotenv file the download status is written to. Overrides CACHE_DOTENV
+        #[arg(long, value_name = "FILE")]
+        dotenv: Option<String>,
+
+        /// Exit with code 3 instead of 0 if no cache was found. Overrides CACHE_FAIL_ON_MISS
+        #[arg(long)]
+        fail_on_miss: bool,
+
+        #[command(flatten)]
+        paths: PathArgs,
+
+        #[command(flatten)]
+        roots: RootArgs
+    },
+    /// Restores all files from the .cache folder to the correct locations and then deletes the .cache folder
+    Restore {
+        #[command(flatten)]
+        paths: PathArgs,
+
+        #[command(flatten)]
+        roots: RootArgs
+    },
+    /// Deletes the local .cache folder
+    #[command(name = "rm-local")]
+    RmLocal,
+    /// Deletes the cache of the current key from the storage backend
+    #[command(name = "rm-remote")]
+    RmRemote {
+        #[command(flatten)]
+        key: KeyArgs
+    },
+    /// Lists all caches of the project that are stored on the storage backend
+    List,
+    /// Prints the completion script for a shell to stdout
+    Completions {
+        /// The shell to generate the completions for
+        shell: Shell
+    }
+}
+
+/// Flags selecting the cache key and archive of a command.
+#[derive(Args, Debug)]
+pub struct KeyArgs {
+    /// The template the cache key is rendered from (e.g.: {os}-{branch}-{hash:Cargo.lock}). Overrides CACHE_KEY
+    #[arg(long)]
+    pub key: Option<String>,
+
+    /// Files whose contents are hashed into the key, seperated by , . Overrides CACHE_KEY_FILES
+    #[arg(long)]
+    pub key_files: Option<String>,
+
+    /// The format of the archive (zip, tar.zst or tar.gz). Overrides CACHE_ARCHIVE_FORMAT
+    #[arg(long, value_parser = parse_archive_format)]
+    pub format: Option<String>
+}
+
+/// Flags adding cached folders and files in addition to the cachepath_ and cachefile_ enviorement variables.
+#[derive(Args, Debug)]
+pub struct PathArgs {
+    /// A folder to cache, may be passed multiple times. Works like a cachepath_ enviorement variable named after the path
+    #[arg(long = "path", value_name = "PATH")]
+    pub paths: Vec<String>,
+
+    /// A file to cache, may be passed multiple times. Works like a cachefile_ enviorement variable named after the file
+    #[arg(long = "file", value_name = "FILE")]
+    pub files: Vec<String>
+}
+
+/// Flags controlling where the cache may be restored to.
+#[derive(Args, Debug)]
+pub struct RootArgs {
+    /// Additional directories the cache may be restored into, seperated like PATH. Overrides CACHE_RESTORE_ROOTS
+    #[arg(long)]
+    pub restore_roots: Option<String>,
+
+    /// Restore caches created on another operating system. Overrides CACHE_CROSS_OS
+    #[arg(long)]
+    pub cross_os: bool,
+
+    /// Pairs of absolute paths mapped between operating systems, seperated by , (e.g.: C:\Users\ci=/home/ci). Overrides CACHE_PATH_REMAP
+    #[arg(long)]
+    pub path_remap: Option<String>
+}
+
+impl Cli {
+    /// [`Cli::parse_args`] parses the launch arguments. The slash arguments of older versions (e.g. `/backup`) are translated
+    /// into the matching subcommands, so existing pipelines keep working.
+    ///
+    /// Returns:
+    /// The parsed [`Cli`]. Exits the program with the usage error code 2 if the arguments are invalid.
+    pub fn parse_args() -> Cli {
+        let launch_args: Vec<String> = env::args().collect();
+        let translated_args = translate_legacy_args(&launch_args);
+
+        if translated_args.is_err(){
+            Cli::command().error(clap::error::ErrorKind::ArgumentConflict, translated_args.unwrap_err()).exit();
+        }
+
+        return Cli::parse_from(translated_args.unwrap());
+    }
+
+    /// [`Cli::apply_env_overrides`] writes the values of all passed flags into the enviorement variables they override,
+    /// since the rest of the program reads it's configuration from there.
+    pub fn apply_env_overrides(&self) {
+        set_env("CACHE_BACKEND", &self.backend);
+        set_env("CI_PROJECT_ID", &self.project_id);
+        set_env("CI_COMMIT_BRANCH", &self.branch);
+
+        match &self.command {
+            Command::Save { key, paths, excludes, compression, force } => {
+                key.apply_env_overrides();
+                paths.apply_env_overrides();
+
+                for (index, exclude) in excludes.iter().enumerate() {
+                    env::set_var(format!("cacheexclude_cli{}", index), exclude);
+                }
+
+                set_env("CACHE_COMPRESSION", compression);
+
+                if *force {
+                    env::set_var("CACHE_FORCE_UPLOAD", "true");
+                }
+            }
+            Command::Download { key, restore_keys, dotenv, fail_on_miss, paths, roots, .. } => {
+                key.apply_env_overrides();
+                paths.apply_env_overrides();
+                roots.apply_env_overrides();
+                set_env("CACHE_RESTORE_KEYS", restore_keys);
+                set_env("CACHE_DOTENV", dotenv);
+
+                if *fail_on_miss {
+                    env::set_var("CACHE_FAIL_ON_MISS", "true");
+                }
+            }
+            Command::Restore { paths, roots } => {
+                paths.apply_env_overrides();
+                roots.apply_env_overrides();
+            }
+            Command::RmRemote { key } => { key.apply_env_overrides(); }
+            Command::RmLocal | Command::List | Command::Completions { .. } => {}
+        }
+    }
+}
+
+impl KeyArgs {
+    fn apply_env_overrides(&self) {
+        set_env("CACHE_KEY", &self.key);
+        set_env("CACHE_KEY_FILES", &self.key_files);
+        set_env("CACHE_ARCHIVE_FORMAT", &self.format);
+    }
+}
+
+impl PathArgs {
+    /// Every path is set as `cachepath_` or `cachefile_` variable named after the path itself (see [`get_entry_name`]),
+    /// so a path is restored independent of the order and number of the flags passed to the save and restore commands.
+    fn apply_env_overrides(&self) {
+        for path in &self.paths {
+            env::set_var(format!("cachepath_{}", get_entry_name(path)), path);
+        }
+
+        for file in &self.files {
+            env::set_var(format!("cachefile_{}", get_entry_name(file)), file);
+        }
+    }
+}
+
+/// [`get_entry_name`] derives the suffix of the enviorement variable a `--path` or `--file` flag is stored in.
+/// The name is `cli_` followed by the path, with every character except letters and digits replaced by `_`, and a short hash of the path,
+/// which keeps apart paths that only differ in the replaced characters. Leading `./`, trailing seperators and `\` seperators don't change the name.
+///
+/// Arguments:
+///
+/// * `path`: type of [`&str`], the value of the flag.
+///
+/// Returns:
+/// The name as [`String`], e.g. `cli_node_modules_dba27c31` for `./node_modules/`.
+fn get_entry_name(path: &str) -> String {
+    let portable_path = path.replace('\\', "/");
+    let mut portable_path = portable_path.trim_end_matches('/');
+
+    while let Some(relative_path) = portable_path.strip_prefix("./") {
+        portable_path = relative_path.trim_start_matches('/');
+    }
+
+    let readable_name: String = portable_path.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let hash = sha256::digest(portable_path);
+
+    return format!("cli_{}_{}", readable_name, &hash[..8]);
+}
+
+impl RootArgs {
+    fn apply_env_overrides(&self) {
+        set_env("CACHE_RESTORE_ROOTS", &self.restore_roots);
+        set_env("CACHE_PATH_REMAP", &self.path_remap);
+
+        if self.cross_os {
+            env::set_var("CACHE_CROSS_OS", "true");
+        }
+    }
+}
+
+/// [`print_completions`] writes the completion script of `shell` to stdout.
+///
+/// Arguments:
+///
+/// * `shell`: type of [`Shell`], the shell to generate the completions for.
+pub fn print_completions(shell: Shell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
+
+/// Sets the enviorement variable `name` if a value was passed for it.
+fn set_env(name: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        env::set_var(name, value);
+    }
+}
+
+/// [`translate_legacy_args`] translates the slash arguments of older versions into subcommands.
+/// `/download /restore` becomes `download --restore`, while combining `/backup` with `/download` or `/restore` is rejected.
+///
+/// Arguments:
+///
+/// * `launch_args`: type of [`&[String]`], the launch arguments including the executable.
+///
+/// Returns:
+/// The translated arguments, unchanged if no slash arguments were used, or an error message if the operations conflict.
+fn translate_legacy_args(launch_args: &[String]) -> Result<Vec<String>, String> {
+    let legacy_args: Vec<&str> = launch_args.iter().skip(1).map(|arg| arg.as_str()).filter(|arg| arg.starts_with('/')).collect();
+    let is_legacy = !legacy_args.is_empty() && legacy_args.iter().all(|arg| {
+        matches!(*arg, "/help" | "/backup" | "/download" | "/restore" | "/rmlocalcache" | "/rmremcache")
+    });
+
+    if !is_legacy {
+        return Ok(launch_args.to_vec());
+    }
+
+    eprintln!("Slash arguments like {} are deprecated. Please use the subcommands listed by --help instead.", legacy_args[0]);
+
+    let mut translated_args = vec![launch_args[0].clone()];
+
+    if legacy_args.contains(&"/help") {
+        translated_args.push(String::from("--help"));
+        return Ok(translated_args);
+    }
+
+    if legacy_args.contains(&"/backup") && (legacy_args.contains(&"/download") || legacy_args.contains(&"/restore")) {
+        return Err(String::from("Cannot backup and restore or backup and download at the same time. Please operate the two in a seperate call."));
+    }
+
+    let operations: Vec<&str> = legacy_args.iter().filter(|arg| **arg != "/restore" || !legacy_args.contains(&"/download")).copied().collect();
+
+    if operations.len() > 1 {
+        return Err(format!("Cannot run {} at the same time. Please operate them in seperate calls.", operations.join(" and ")));
+    }
+
+    match operations[0] {
+        "/backup" => translated_args.push(String::from("save")),
+        "/download" => {
+            translated_args.push(String::from("download"));
+
+            if legacy_args.contains(&"/restore") {
+                translated_args.push(String::from("--restore"));
+            }
+        }
+        "/restore" => translated_args.push(String::from("restore")),
+        "/rmlocalcache" => translated_args.push(String::from("rm-local")),
+        _ => translated_args.push(String::from("rm-remote"))
+    }
+
+    return Ok(translated_args);
+}
+
+/// Validates the value of `--backend`.
+fn parse_backend(value: &str) -> Result<String, String> {
+    if enums::BackendType::from_name(value).is_none() {
+        return Err(String::from("supported backends are: webdav, ftp, ftps, s3, fs"));
+    }
+
+    return Ok(value.to_string());
+}
+
+/// Validates the value of `--format`.
+fn parse_archive_format(value: &str) -> Result<String, String> {
+    if enums::ArchiveFormat::from_name(value).is_none() {
+        return Err(String::from("supported formats are: zip, tar.zst, tar.gz"));
+    }
+
+    return Ok(value.to_string());
+}
+
+/// Validates the value of `--compression`.
+fn parse_compression(value: &str) -> Result<String, String> {
+    let compression = structs::Compression::parse(value);
+
+    if compression.is_err() {
+        return Err(compression.unwrap_err().to_string());
+    }
+
+    return Ok(value.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use clap::Parser;
+
+    use super::{get_entry_name, translate_legacy_args, Cli};
+    use crate::storage::memory::MemoryStore;
+    use crate::testutils::{self, TestEnv};
+    use crate::{create_cache, download_cache};
+
+    /// Parses `args` like launch arguments and applies them to the enviorement variables. Flags of an earlier call are removed first.
+    fn apply_args(args: &[&str]) {
+        let cli_vars: Vec<String> = env::vars().map(|(name, _)| name).filter(|name| name.starts_with("cachepath_") || name.starts_with("cachefile_")).collect();
+
+        for name in cli_vars {
+            env::remove_var(name);
+        }
+
+        Cli::try_parse_from([&["worker-cache"], args].concat()).unwrap().apply_env_overrides();
+    }
+
+    /// Translates the launch arguments `args`, the executable is added in front of them.
+    fn translate(args: &[&str]) -> Result<Vec<String>, String> {
+        let launch_args: Vec<String> = [&["worker-cache"], args].concat().iter().map(|arg| arg.to_string()).collect();
+        return translate_legacy_args(&launch_args).map(|translated_args| translated_args[1..].to_vec());
+    }
+
+    #[test]
+    fn legacy_args_are_translated_to_subcommands() {
+        assert_eq!(translate(&["/backup"]).unwrap(), vec!["save"]);
+        assert_eq!(translate(&["/download", "/restore"]).unwrap(), vec!["download", "--restore"]);
+        assert_eq!(translate(&["/rmlocalcache"]).unwrap(), vec!["rm-local"]);
+        assert_eq!(translate(&["/rmremcache"]).unwrap(), vec!["rm-remote"]);
+        assert!(translate(&["/backup", "/download"]).is_err());
+
+        // Only the slash arguments of older versions are translated, listing the caches is only available as subcommand.
+        assert_eq!(translate(&["/listremcache"]).unwrap(), vec!["/listremcache"]);
+        assert!(Cli::try_parse_from(["worker-cache", "/listremcache"]).is_err());
+    }
+
+    #[test]
+    fn entry_names_are_derived_from_the_path() {
+        assert_eq!(get_entry_name("node_modules"), get_entry_name("./node_modules/"));
+        assert_eq!(get_entry_name("target/debug"), get_entry_name(".\\target\\debug"));
+        assert!(get_entry_name("target/debug").starts_with("cli_target_debug_"));
+        assert_ne!(get_entry_name("target/debug"), get_entry_name("target_debug"));
+    }
+
+    #[test]
+    fn paths_are_restored_independent_of_the_flag_order() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        test_env.write("first/a.txt", "a");
+        test_env.write("second/b.txt", "b");
+        apply_args(&["save", "--path", "first", "--path", "second"]);
+        assert!(create_cache::main(&store).is_ok());
+        fs::remove_dir_all(test_env.path().join("first")).unwrap();
+        fs::remove_dir_all(test_env.path().join("second")).unwrap();
+
+        apply_args(&["download", "--restore", "--path", "second"]);
+        let download_result = download_cache::main(&store, true);
+
+        assert!(download_result.is_ok(), "{}", download_result.unwrap_err());
+        assert_eq!(testutils::read(&test_env.path().join("second/b.txt")), "b");
+        assert!(!test_env.path().join("first").exists());
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::BTreeMap, env, fs, path::{Path, PathBuf}, process};
+
+use serde::Deserialize;
+
+use crate::datas::errors::{self, CacheError};
+
+/// Config files that are used if no config file was passed, in the order they are searched for.
+const DEFAULT_CONFIG_FILES: [&str; 3] = ["worker-cache.toml", "worker-cache.yaml", "worker-cache.yml"];
+
+/// [`Config`] describes the contents of a `worker-cache.toml` or `worker-cache.yaml` file.
+/// Every setting maps to one of the enviorement variables documented in `--help`. Enviorement variables and flags take
+/// precedence over the config file, so the file is only used for settings that are not set otherwise.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    backend: Option<String>,
+    webdav: WebDavConfig,
+    ftp: FtpConfig,
+    s3: S3Config,
+    fs: FileSystemConfig,
+    key: KeyConfig,
+    archive: ArchiveConfig,
+    restore: RestoreConfig,
+    download: DownloadConfig,
+    paths: BTreeMap<String, String>,
+    files: BTreeMap<String, String>,
+    excludes: BTreeMap<String, String>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct WebDavConfig {
+    address: Option<String>,
+    user: Option<ConfigValue>,
+    password: Option<ConfigValue>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FtpConfig {
+    address: Option<String>,
+    user: Option<ConfigValue>,
+    password: Option<ConfigValue>,
+    secure: Option<bool>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct S3Config {
+    endpoint: Option<String>,
+    bucket: Option<String>,
+    prefix: Option<String>,
+    region: Option<String>,
+    access_key: Option<ConfigValue>,
+    secret_key: Option<ConfigValue>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileSystemConfig {
+    root: Option<String>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct KeyConfig {
+    template: Option<String>,
+    files: Vec<String>,
+    restore: Vec<String>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ArchiveConfig {
+    format: Option<String>,
+    compression: Option<String>,
+    force: Option<bool>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RestoreConfig {
+    roots: Vec<String>,
+    cross_os: Option<bool>,
+    remap: Vec<String>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DownloadConfig {
+    dotenv: Option<String>,
+    fail_on_miss: Option<bool>
+}
+
+/// A value that is either written into the config file directly or read from another source.
+/// Used for credentials, so they don't have to be commited together with the config file.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ConfigValue {
+    Value(String),
+    Source {
+        /// Name of an enviorement variable containing the value (e.g. a masked gitlab variable).
+        env: Option<String>,
+        /// Path of a file containing the value (e.g. a mounted secret).
+        file: Option<String>
+    }
+}
+
+impl ConfigValue {
+    /// Resolves the value from it's source.
+    ///
+    /// Returns:
+    /// The value, [`None`] if the referenced enviorement variable is not set or an error if the file could not be read.
+    fn resolve(&self) -> Result<Option<String>, CacheError> {
+        match self {
+            ConfigValue::Value(value) => { return Ok(Some(value.clone())); }
+            ConfigValue::Source { env: Some(name), file: None } => { return Ok(env::var(name).ok()); }
+            ConfigValue::Source { env: None, file: Some(path) } => {
+                let value = fs::read_to_string(path);
+
+                if value.is_err(){
+                    return Err(CacheError::io("Could not read a credential file referenced in the config file.", value.unwrap_err()));
+                }
+
+                return Ok(Some(value.unwrap().trim_end_matches(['\r', '\n']).to_string()));
+            }
+            ConfigValue::Source { .. } => { return Err(CacheError::config("Credentials in the config file need exactly one of env or file as source.")); }
+        }
+    }
+}
+
+impl Config {
+    /// [`Config::load`] reads and parses a config file. Files ending with `.yaml` or `.yml` are parsed as YAML, all others as TOML.
+    ///
+    /// Arguments:
+    ///
+    /// * `path`: type of [`&Path`], the config file to read.
+    ///
+    /// Returns:
+    /// The parsed [`Config`] or an error if the file could not be read or is invalid.
+    pub fn load(path: &Path) -> Result<Config, CacheError> {
+        let contents = fs::read_to_string(path);
+
+        if contents.is_err(){
+            return Err(CacheError::io(format!("Could not read the config file {}.", path.display()).as_str(), contents.unwrap_err()));
+        }
+
+        let contents = contents.unwrap();
+        let is_yaml = path.extension().is_some_and(|extension| extension == "yaml" || extension == "yml");
+
+        if is_yaml {
+            return serde_yaml::from_str(contents.as_str()).map_err(|error| CacheError::Config(format!("The config file {} is invalid: {}", path.display(), error)));
+        }
+
+        return toml::from_str(contents.as_str()).map_err(|error| CacheError::Config(format!("The config file {} is invalid: {}", path.display(), error)));
+    }
+
+    /// [`Config::apply_to_env`] sets the enviorement variables of all settings in the config file that are not already set.
+    /// This gives the precedence flags > enviorement variables > config file > defaults.
+    ///
+    /// Returns:
+    /// An error if a credential could not be resolved.
+    pub fn apply_to_env(&self) -> Result<(), CacheError> {
+        let mut values: Vec<(String, Option<String>)> = vec![
+            (String::from("CACHE_BACKEND"), self.backend.clone()),
+            (String::from("WEBDAVADDR"), self.webdav.address.clone()),
+            (String::from("FTPADDR"), self.ftp.address.clone()),
+            (String::from("FTPS"), self.ftp.secure.map(|secure| secure.to_string())),
+            (String::from("S3ENDPOINT"), self.s3.endpoint.clone()),
+            (String::from("S3BUCKET"), self.s3.bucket.clone()),
+            (String::from("S3PREFIX"), self.s3.prefix.clone()),
+            (String::from("S3REGION"), self.s3.region.clone()),
+            (String::from("FSROOT"), self.fs.root.clone()),
+            (String::from("CACHE_KEY"), self.key.template.clone()),
+            (String::from("CACHE_KEY_FILES"), join_list(&self.key.files)),
+            (String::from("CACHE_RESTORE_KEYS"), join_list(&self.key.restore)),
+            (String::from("CACHE_ARCHIVE_FORMAT"), self.archive.format.clone()),
+            (String::from("CACHE_COMPRESSION"), self.archive.compression.clone()),
+            (String::from("CACHE_FORCE_UPLOAD"), self.archive.force.map(|force| force.to_string())),
+            (String::from("CACHE_DOTENV"), self.download.dotenv.clone()),
+            (String::from("CACHE_FAIL_ON_MISS"), self.download.fail_on_miss.map(|fail_on_miss| fail_on_miss.to_string())),
+            (String::from("CACHE_CROSS_OS"), self.restore.cross_os.map(|cross_os| cross_os.to_string())),
+            (String::from("CACHE_PATH_REMAP"), join_list(&self.restore.remap))
+        ];
+
+        let credentials = [
+            ("WEBDAVUSER", &self.webdav.user),
+            ("WEBDAVPASS", &self.webdav.password),
+            ("FTPUSER", &self.ftp.user),
+            ("FTPPASS", &self.ftp.password),
+            ("S3ACCESSKEY", &self.s3.access_key),
+            ("S3SECRETKEY", &self.s3.secret_key)
+        ];
+
+        for (name, credential) in credentials {
+            // Only resolve credentials that are actually used, so a missing secret of another backend is not an error.
+            if credential.is_none() || env::var_os(name).is_some() {
+                continue;
+            }
+
+            let value = credential.as_ref().unwrap().resolve();
+
+            if value.is_err(){
+                return Err(value.unwrap_err());
+            }
+
+            values.push((String::from(name), value.unwrap()));
+        }
+
+        if !self.restore.roots.is_empty() {
+            let roots = env::join_paths(&self.restore.roots);
+
+            if roots.is_err(){
+                return Err(CacheError::config("The restore roots in the config file contain a path seperator."));
+            }
+
+            values.push((String::from("CACHE_RESTORE_ROOTS"), Some(roots.unwrap().to_string_lossy().to_string())));
+        }
+
+        values.extend(self.paths.iter().map(|(name, path)| (format!("cachepath_{}", name), Some(path.clone()))));
+        values.extend(self.files.iter().map(|(name, path)| (format!("cachefile_{}", name), Some(path.clone()))));
+        values.extend(self.excludes.iter().map(|(name, pattern)| (format!("cacheexclude_{}", name), Some(pattern.clone()))));
+
+        for (name, value) in values {
+            if let Some(value) = value {
+                if env::var_os(&name).is_none() {
+                    env::set_var(&name, value);
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Joins a list of the config file into the `,` seperated format of the enviorement variables.
+fn join_list(list: &[String]) -> Option<String> {
+    if list.is_empty() {
+        return None;
+    }
+
+    return Some(list.join(","));
+}
+
+/// [`find_config_file`] returns the config file to use. A path passed via `--config` or the `CACHE_CONFIG` enviorement variable
+/// is used as is, otherwise the first of [`DEFAULT_CONFIG_FILES`] that exists in the current directory.
+///
+/// Arguments:
+///
+/// * `config_path`: type of [`Option<String>`], the path passed via `--config`.
+///
+/// Returns:
+/// The path of the config file or [`None`] if no config file is used.
+fn find_config_file(config_path: Option<String>) -> Option<PathBuf> {
+    let config_path = config_path.or(env::var("CACHE_CONFIG").ok()).filter(|path| !path.trim().is_empty());
+
+    if config_path.is_some() {
+        return config_path.map(PathBuf::from);
+    }
+
+    return DEFAULT_CONFIG_FILES.iter().map(PathBuf::from).find(|path| path.is_file());
+}
+
+/// [`load_config`] loads the config file (see [`find_config_file`]) and applies it to the enviorement variables.
+/// Exits the program if the config file is invalid.
+///
+/// Arguments:
+///
+/// * `config_path`: type of [`Option<String>`], the path passed via `--config`.
+pub fn load_config(config_path: Option<String>) {
+    let config_file = find_config_file(config_path);
+
+    if config_file.is_none() {
+        return;
+    }
+
+    let config_file = config_file.unwrap();
+    let config = Config::load(&config_file);
+
+    if config.is_err(){
+        eprintln!("{}", config.unwrap_err());
+        process::exit(errors::EXIT_CONFIG);
+    }
+
+    let apply_result = config.unwrap().apply_to_env();
+
+    if apply_result.is_err(){
+        eprintln!("Could not apply the config file {}. Error was: {}", config_file.display(), apply_result.unwrap_err());
+        process::exit(errors::EXIT_CONFIG);
+    }
+
+    println!("Loaded config file {}", config_file.display());
+}
@@ -1,30 +1,30 @@
-use std::fs::File;
-use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::{env, fs, str::FromStr, ops::Deref};
+use std::{env, fs, ops::Deref};
 use crate::datas::structs::{self, RestoreData};
 use crate::datas::enums;
-use crate::{envfuncs, helpers};
-use crate::zip::zip_dir_recursively;
+use crate::datas::errors::CacheError;
+use crate::{digest, envfuncs, globs, helpers};
+use crate::zip::zip_restore_data;
+use crate::tar::tar_restore_data;
+use crate::storage::{self, CacheStore};
 use normpath::PathExt;
-use rustydav::client::{self, Client};
 
-/// Fetches cached values via enviorement variables, moves the files from there to the .cache folder, zips it's contents and then uploads the zipped contents to a
-/// webdav server.
+/// Fetches cached values via enviorement variables, archives them straight from their location and then uploads the archive to
+/// the storage backend.
 /// 
 /// Arguments:
 /// 
-/// * `ostype`: Is of type [`enums::OsType`]. Represents the type of operating system.
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the cache is uploaded to.
 /// 
 /// Returns:
-/// Returns an error code if it fails, indicating the location of an error.
-pub fn main(ostype: enums::OsType) -> Result<(),i32>{
+/// Returns the error if it fails.
+pub fn main(store: &dyn CacheStore) -> Result<(), CacheError>{
     println!("Welcome to the cache upload tool. This will create a cache structure for the specified values.
     To define more items to cache simply create more enviorement variables with cachepath_ or cachefile_ in front of them. 
     We take the local file system into account so full paths are not required.");
     println!("Current directory is: {}", env::current_dir().unwrap().to_str().unwrap());
-    let folders = envfuncs::get_env_if_startswith("cachepath_");
-    let files = envfuncs::get_env_if_startswith("cachefile_");
+    let folders = globs::expand_paths(envfuncs::get_env_with_suffix("cachepath_"), true);
+    let files = globs::expand_paths(envfuncs::get_env_with_suffix("cachefile_"), false);
 
     if (folders.len() == 0)  && (files.len() == 0){
         eprintln!("Found 0 directories or files via searching env vars that start with cachepath_ or cachefile_. \n
@@ -34,152 +34,172 @@ pub fn main(ostype: enums::OsType) -> Result<(),i32>{
     if files.is_empty() && folders.is_empty(){
         eprintln!("Could not find any files or folders to restore. Please ensure that at least one enviorement variable starting with the name: cachepath_ or cachefile_ 
         and the value of a path is set.");
-        return Err(13);
+        return Err(CacheError::config("Could not find any files or folders to cache."));
     }
 
     let restore_data = generate_storage_data_from_pathstrings(files, folders);
 
     if restore_data.is_err(){
-        eprintln!("Encountered an error while generating the Restore Data. The error was: {}", restore_data.unwrap_err());
-        return Err(14);
+        eprintln!("Encountered an error while generating the Restore Data.");
+        return Err(restore_data.unwrap_err());
     }
 
     let restore_data = restore_data.unwrap();
     println!("Found {} Files and folders overall and created their Data objects. Uploading to server now.", restore_data.len());
-    let json_data = serde_json::to_string_pretty(&restore_data).unwrap(); 
-    println!("Checking if base & project directory exists on webdav server and creating it if it doesn't now.");
-    let create_result = create_webdav_paths();
+    let json_data = serde_json::to_string_pretty(&restore_data);
 
-    if create_result.is_err(){
-        eprintln!("Encountered error while attempting to create base and project directory on webdav server. Error was: \n{}", create_result.unwrap_err());
-        return Err(15);
+    if json_data.is_err(){
+        return Err(CacheError::serde("Could not serialize the restore data into the data.json file.", json_data.unwrap_err()));
     }
 
-    let cpy_files_result = cpy_files_to_cache_dir(json_data, restore_data);
+    let json_data = json_data.unwrap();
+    let archive_file_name = envfuncs::get_archive_file_name();
+    let cache_digest = digest::compute_digest(json_data.as_str(), &restore_data, &globs::ExcludeRules::from_env());
 
-    if cpy_files_result.is_err(){
-        eprintln!("Encountered an error while attempting to copy files to the .cache directory. Error was: \n{}", cpy_files_result.unwrap_err());
-        return Err(16);
+    if cache_digest.is_err(){
+        eprintln!("Encountered an error while calculating the digest of the cached files.");
+        return Err(cache_digest.unwrap_err());
     }
 
+    let cache_digest = cache_digest.unwrap();
+
+    if !envfuncs::get_force_upload(){
+        let unchanged_result = digest::is_unchanged(store, archive_file_name.as_str(), cache_digest.as_str());
 
-    let zip_cache_result = zip_cache_dir(ostype);
+        if unchanged_result.is_err(){
+            eprintln!("Encountered an error while checking if the cache on the storage backend is up to date.");
+            return Err(unchanged_result.unwrap_err());
+        }
 
-    if zip_cache_result.is_err(){
-        eprintln!("Encountered error while attempting to Zip cache folder. Error was: \n{}", zip_cache_result.unwrap_err());
-        return Err(17);
+        if unchanged_result.unwrap(){
+            println!("Skipping the upload since the contents of the cache did not change (digest {}). Set CACHE_FORCE_UPLOAD=true to upload it anyway.", cache_digest);
+            return Ok(());
+        }
     }
 
-    let copyres = upload_zip();
+    println!("Checking if base & project directory exists on the storage backend and creating it if it doesn't now.");
+    let create_result = create_remote_paths(store);
 
-    if copyres.is_err(){
-        eprintln!("Encountered an error while attempting to upload the ZIP file to the webdav server. Error was: \n{}", copyres.unwrap_err());
-        return Err(18);
+    if create_result.is_err(){
+        eprintln!("Encountered error while attempting to create base and project directory on the storage backend.");
+        return create_result;
     }
 
-    println!("Finished uploading cache to webdav.");
+    let work_dir = envfuncs::get_work_dir();
+
+    if work_dir.is_err(){
+        eprintln!("Encountered an error while creating the work directory the archive is written to.");
+        return Err(work_dir.unwrap_err());
+    }
+
+    // The archive is written outside of the project, so it is never part of a cached folder or left behind in the project.
+    let archive_file = work_dir.unwrap().join(archive_file_name.as_str());
+    let upload_result = create_and_upload_archive(store, json_data.as_str(), &restore_data, cache_digest.as_str(), &archive_file);
+
+    // The archive is only needed for the upload, so it is removed whether the upload succeeded or not.
+    if archive_file.exists() && fs::remove_file(&archive_file).is_err(){
+        println!("Could not remove the archive {}. Ignoring since it is replaced by the next upload.", archive_file.display());
+    }
+
+    if upload_result.is_err(){
+        return Err(upload_result.unwrap_err());
+    }
+
+    digest::record_digest(archive_file_name.as_str(), cache_digest.as_str());
+    println!("Finished uploading cache to the storage backend.");
     return Ok(());
 }
 
 /// [`generate_storage_data_from_pathstrings`] generates a [`Vec<enums::RestoreData>`] from a list of file and folder paths.
+/// Every object is named after the suffix of the enviorement variable it was configured with, which is used to find it when restoring.
 /// 
 /// Arguments:
 /// 
-/// * `files`: A vector of strings representing file paths.
-/// * `folders`: A vector of strings representing the paths to folders.
+/// * `files`: A vector of tuples containing the suffix of the enviorement variable and the file path.
+/// * `folders`: A vector of tuples containing the suffix of the enviorement variable and the folder path.
 /// 
 /// Returns:
 /// 
 /// Returns a [`Result`] enum. If successful in generating the restore data, 
-/// returns [`Vec<enums::RestoreData>`] objects. If there is an error, returns a static string
-/// message describing the error.
-fn generate_storage_data_from_pathstrings(files: Vec<String>, folders: Vec<String>) -> Result<Vec<RestoreData>, &'static str> {
+/// returns [`Vec<enums::RestoreData>`] objects. If there is an error, returns a [`CacheError`] describing the error.
+fn generate_storage_data_from_pathstrings(files: Vec<(String, String)>, folders: Vec<(String, String)>) -> Result<Vec<RestoreData>, CacheError> {
     let mut restore_data:Vec<structs::RestoreData> = Vec::new();
 
     if folders.is_empty() && files.is_empty(){
-        return Err("Both files and folders were empty. We need data to generate the restoredata objects");
+        return Err(CacheError::config("Both files and folders were empty. We need data to generate the restoredata objects"));
     }
 
-    for folder in folders{
+    for (entry_name, folder) in folders{
         let restoredir = generate_storage_data_directory_from_pathstr(folder.as_str());
 
-        if let Ok(restoredir) = restoredir {
+        if let Ok(mut restoredir) = restoredir {
+            restoredir.entry_name = Some(entry_name);
             restore_data.push(restoredir);
         }else if let Err(restoredir) = restoredir {
             println!("Could not add the folder at:\n{}\n Encountered error: {}", folder,restoredir);
         }
     }
 
-    for file in files{
+    for (entry_name, file) in files{
         let restorefile = generate_storage_data_file_from_pathstr(file.clone());
 
-        if let Ok(restorefile) = restorefile {
+        if let Ok(mut restorefile) = restorefile {
+            restorefile.entry_name = Some(entry_name);
             restore_data.push(restorefile);
         }else if let Err(restorefile) = restorefile {
             println!("Could not add the folder at:\n{}\n Encountered error: {}", file,restorefile);
         }
     }
 
+    // Excluded objects are removed before the names are assigned, so they are neither copied nor listed in the data.json.
+    let excludes = globs::ExcludeRules::from_env();
+    restore_data.retain(|restore_obj| {
+        let restore_objpath = restore_obj.get_restore_to(&[]).join(&restore_obj.restore_obj_name);
+        let excluded = excludes.is_excluded(&restore_objpath);
+
+        if excluded {
+            println!("Skipping {} since it matches an exclude pattern.", restore_objpath.display());
+        }
+
+        return !excluded;
+    });
+
+    assign_unique_cache_names(&mut restore_data);
+
     let numresdata = restore_data.len();
 
     if numresdata == 0{
-        return Err("No Restore data found");
+        return Err(CacheError::config("No Restore data found. None of the configured files and folders could be cached."));
     }
 
     return Ok(restore_data)
 }
 
-/// `cpy_files_to_cache_dir` copies files and directories to a cache directory
+/// [`assign_unique_cache_names`] makes sure every object gets it's own name inside of the .cache folder.
+/// Objects with the same name (e.g. multiple `node_modules` folders matched by a glob pattern) get a numbered suffix.
 /// 
 /// Arguments:
 /// 
-/// * `json_data`: type of <string>. 
-/// JSON Data that should be written to restore the cache later on should represent `restore_data`
-/// * `restore_data`: type of`Vec<enums::RestoreData>`. 
-/// Contains the data we want to restore later on. `json_data` should represent it's contents.
-/// 
-/// Returns:
-/// 
-/// The function `cpy_files_to_cache_dir` returns a `Result<(), &'static str>`.
-fn cpy_files_to_cache_dir(json_data: String, restore_data: Vec<structs::RestoreData>) -> Result<(), &'static str>{
-    if Path::new(".cache").exists(){
-        return Err("We already found a folder named .cache. 
-        Aborting upload since this may cause issues / conflics. 
-        Please make sure you don't have a project in your repository with that name.");
-    }
-
-    let failed_create_dir = fs::create_dir_all(".cache").is_err();
-    
-    if failed_create_dir {
-        return Err("Could not create cache dir at .cache. 
-        Please ensure we have write permissions in the current directory you work in and that the folder doesn't already exist.");
-    }
-
-    let failed_write_json = fs::write(".cache/data.json", json_data).is_err();
-
-    if failed_write_json {
-        return Err("Could not write json data to .cache/data.json.");
-    }
-
-    let current_directory = env::current_dir().unwrap();
-
-    for restore_obj in restore_data{
+/// * `restore_data`: type of [`&mut [RestoreData]`], the objects to cache.
+fn assign_unique_cache_names(restore_data: &mut [RestoreData]){
+    // data.json is reserved for the restore data itself.
+    let mut used_names: Vec<String> = vec![String::from("data.json")];
 
-        let restore_objpath = format!("{}/{}", restore_obj.restore_to, restore_obj.restore_obj_name);
-        let copy_to_path = format!("{}/.cache/{}", current_directory.to_str().unwrap(), restore_obj.restore_obj_name);
-        let copy_error: bool;
+    for restore_obj in restore_data.iter_mut(){
+        let mut cache_name = restore_obj.restore_obj_name.clone();
+        let mut suffix = 1;
 
-        match restore_obj.cachetype{
-            enums::CacheType::Directory => {copy_error = helpers::copy_recursively(restore_objpath.clone(), copy_to_path.clone()).is_err();}
-            enums::CacheType::File => {copy_error = fs::copy(restore_objpath.clone(), copy_to_path.clone()).is_err();}
+        while used_names.contains(&cache_name){
+            cache_name = format!("{}-{}", restore_obj.restore_obj_name, suffix);
+            suffix += 1;
         }
-    
-        if copy_error{
-            println!("Failed to copy file from {} to {}. This may result in the file not being restored in a later process.", restore_objpath, copy_to_path);
+
+        if cache_name != restore_obj.restore_obj_name{
+            restore_obj.cache_name = Some(cache_name.clone());
         }
-    }
 
-    return Ok(());
+        used_names.push(cache_name);
+    }
 }
 
 /// Generates the storage data file from a given file path string.
@@ -191,8 +211,8 @@ fn cpy_files_to_cache_dir(json_data: String, restore_data: Vec<structs::RestoreD
 /// Returns:
 /// 
 /// Returns a [`Result`] type. If the function is successful, it returns a [`structs::RestoreData`] object. If there
-/// is an error, it returns a [`String`] representing the error error message.
-fn generate_storage_data_file_from_pathstr(file: String) -> Result<structs::RestoreData, &'static str> {
+/// is an error, it returns a [`CacheError`] describing the error.
+fn generate_storage_data_file_from_pathstr(file: String) -> Result<structs::RestoreData, CacheError> {
     let file_path = Path::new(&file).normalize();
 
     if file_path.is_err(){
@@ -216,29 +236,34 @@ fn generate_storage_data_file_from_pathstr(file: String) -> Result<structs::Rest
 /// Returns:
 /// 
 /// Returns a [`Result`] type. If the function is successful, it returns a [`structs::RestoreData`] object. If there
-/// is an error, it returns a [`String`] representing the error error message.
-fn generate_storage_data_file_from_pathbuf(file: PathBuf) ->  Result<structs::RestoreData, &'static str>{
-    if !fs::metadata(file.clone()).is_ok(){
-        return Result::Err("Could not find directory at location");
+/// is an error, it returns a [`CacheError`] describing the error.
+fn generate_storage_data_file_from_pathbuf(file: PathBuf) ->  Result<structs::RestoreData, CacheError>{
+    let metadata_result = fs::metadata(file.clone());
+
+    if metadata_result.is_err(){
+        return Result::Err(CacheError::io("Could not find the file at location", metadata_result.unwrap_err()));
     }
 
     if file.is_dir(){
-        return Result::Err("We recieved a folder when we expected a file... please make sure you use the right prefix");
+        return Result::Err(CacheError::config("We recieved a folder when we expected a file... please make sure you use the right prefix"));
     }
 
     let parent_path = file.deref().parent();
     
     if parent_path.is_none(){
-        return Err("Could not access parent directory. Please validate you're trying to cache a valid folder (and have permissiosn to access the directory to put it back into)");
+        return Err(CacheError::config("Could not access parent directory. Please validate you're trying to cache a valid folder (and have permissiosn to access the directory to put it back into)"));
     }
 
-    let parent_path = parent_path.unwrap().to_str().unwrap();
     let restore_object_name = file.file_name().unwrap().to_os_string().into_string().unwrap();
-    let parent_path_string = String::from_str(parent_path).unwrap();
+    let (base, restore_to) = get_restore_location(parent_path.unwrap());
     let res_data: structs::RestoreData = structs::RestoreData { 
         restore_obj_name: restore_object_name, 
         cachetype: enums::CacheType::File,
-        restore_to: parent_path_string
+        restore_to,
+        base: Some(base),
+        cache_name: None,
+        entry_name: None,
+        os: Some(enums::OsType::get_ostype())
     };
 
     println!("Created storage data for file: {} successfully", res_data.restore_obj_name);
@@ -257,8 +282,8 @@ fn generate_storage_data_file_from_pathbuf(file: PathBuf) ->  Result<structs::Re
 /// 
 /// Returns a [`Result`] type. 
 /// If the function is successful, it returns a [`structs::RestoreData`] object. If there is an
-/// error, it returns  a [`String`] error message.
-fn generate_storage_data_directory_from_pathstr(directory: &str) -> Result<structs::RestoreData, &'static str> {
+/// error, it returns a [`CacheError`] describing the error.
+fn generate_storage_data_directory_from_pathstr(directory: &str) -> Result<structs::RestoreData, CacheError> {
     let dir_canonicalize_result = Path::new(directory).normalize();
 
     if dir_canonicalize_result.is_err(){
@@ -281,198 +306,209 @@ fn generate_storage_data_directory_from_pathstr(directory: &str) -> Result<struc
 /// Returns:
 /// 
 /// Returns a [`Result`] type. If the function is successful, it returns a [`structs::RestoreData`] object. If there
-/// is an error, it returns a [`String`] representing the error error message.
-fn generate_storage_data_directory_from_pathbuf(dir: PathBuf) ->  Result<structs::RestoreData, &'static str>{
-    if !fs::metadata(dir.clone()).is_ok(){
-        return Result::Err("Could not find directory");
+/// is an error, it returns a [`CacheError`] describing the error.
+fn generate_storage_data_directory_from_pathbuf(dir: PathBuf) ->  Result<structs::RestoreData, CacheError>{
+    let metadata_result = fs::metadata(dir.clone());
+
+    if metadata_result.is_err(){
+        return Result::Err(CacheError::io("Could not find directory", metadata_result.unwrap_err()));
     }
 
     if dir.is_file(){
-        return Result::Err("We recieved a file when we expected a folder... please make sure you use the right prefix");
+        return Result::Err(CacheError::config("We recieved a file when we expected a folder... please make sure you use the right prefix"));
     }
 
     let parentpath = dir.deref().parent();
 
     if parentpath.is_none(){
-        return Err("Could not access parent directory. Please validate you're trying to cache a valid folder (and have permissiosn to access the directory to put it back into)");
+        return Err(CacheError::config("Could not access parent directory. Please validate you're trying to cache a valid folder (and have permissiosn to access the directory to put it back into)"));
     }
 
-    let parent_path = parentpath.unwrap().to_str().unwrap();
     let restore_object_name = dir.file_name().unwrap().to_os_string().into_string().unwrap();
-    let parentpathstr = &String::from_str(parent_path).unwrap();
+    let (base, restore_to) = get_restore_location(parentpath.unwrap());
     let res_data: structs::RestoreData = structs::RestoreData { 
         restore_obj_name: restore_object_name, 
         cachetype: enums::CacheType::Directory,  
-        restore_to: parentpathstr.to_string() 
+        restore_to,
+        base: Some(base),
+        cache_name: None,
+        entry_name: None,
+        os: Some(enums::OsType::get_ostype())
     };
 
     println!("Created storage data for folder: {} successfully", res_data.restore_obj_name);
     return Result::Ok(res_data);
 }
 
-/// [`upload_zip`] uploads a zip file to a webdav server using the provided credentials and
-/// file path.
+/// [`get_restore_location`] returns the restore location stored in the data.json file for an object located in `parent_path`.
+/// Locations inside of the project directory are stored relative to it, followed by locations inside of the home directory,
+/// so the cache can be restored on runners that check the project out to a different directory or use a different user.
+/// 
+/// Arguments:
+/// 
+/// * `parent_path`: type of [`&Path`], the normalized directory containing the cached object.
 /// 
 /// Returns:
 /// 
-/// The function [`upload_zip()`] returns an error message if there is a problem.
-fn upload_zip() -> Result<(), &'static str>{
-    let webdav_address = envfuncs::get_webdavaddr();
-    let webdav_client = client::Client::init(&envfuncs::get_webdav_user(), &envfuncs::get_webdav_password());
-    let file = File::open(envfuncs::get_zip_file_name());
+/// The base directory and the portable location relative to it (`.` for the base directory itself, see [`helpers::to_portable_path`]).
+/// Locations outside of both directories are returned as absolute path with [`enums::PathBase::Absolute`].
+fn get_restore_location(parent_path: &Path) -> (enums::PathBase, String) {
+    let base_dirs = [(enums::PathBase::Project, Some(envfuncs::get_project_dir())), (enums::PathBase::Home, envfuncs::get_home_dir())];
+
+    for (base, base_dir) in base_dirs {
+        let Some(base_dir) = base_dir else { continue };
+        let base_dir = base_dir.normalize().map(|base_dir| base_dir.into_path_buf()).unwrap_or(base_dir);
 
-    if file.is_err(){
-        return Err("Could not open file to read bytes into stream This maybe due to the zip file having been deleted since creation");
+        match parent_path.strip_prefix(&base_dir) {
+            Ok(relative_path) if relative_path.as_os_str().is_empty() => return (base, String::from(".")),
+            Ok(relative_path) => return (base, helpers::to_portable_path(relative_path)),
+            Err(_) => continue
+        }
     }
 
-    let file = file.unwrap();
-    let mut reader = BufReader::new(file);
-    let mut buffer = Vec::new();
+    return (enums::PathBase::Absolute, helpers::to_portable_path(parent_path));
+}
 
-    // Read file into vector.
-    let readerr = reader.read_to_end(&mut buffer).is_err();
+/// [`create_and_upload_archive`] archives the cached objects into `archive_file`, writes the checksum into it's header and uploads it.
+/// 
+/// Arguments:
+/// 
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the archive is uploaded to.
+/// * `json_data`: type of [`&str`]. The contents of the data.json file, should represent `restore_data`.
+/// * `restore_data`: type of [`&[RestoreData]`]. The objects that are archived.
+/// * `cache_digest`: type of [`&str`]. The digest of the cached contents, it is written into the header of the archive.
+/// * `archive_file`: type of [`&Path`]. The file the archive is written to before it is uploaded.
+/// 
+/// Returns:
+/// 
+/// An error if archiving or uploading failed. The archive is not removed on errors.
+fn create_and_upload_archive(store: &dyn CacheStore, json_data: &str, restore_data: &[RestoreData], cache_digest: &str, archive_file: &Path) -> Result<(), CacheError>{
+    // The digest is part of the uploaded archive, so a download never sees an archive with the digest of another upload.
+    let header = digest::create_header(cache_digest);
+    let archive_result = archive_restore_data(json_data, restore_data, archive_file, envfuncs::get_archive_format(), envfuncs::get_compression(), &header);
 
-    if readerr {
-        return Err("Could not read zip file. This maybe due to insufficient permissions or because of other similar reasons.");
+    if archive_result.is_err(){
+        eprintln!("Encountered error while attempting to archive the cached files and folders.");
+        return archive_result;
     }
 
-    let upload_path = format!("{}/gitcache/{}/{}", webdav_address, envfuncs::get_projectid(), envfuncs::get_zip_file_name());
-    let upload_result = webdav_client.put(
-        buffer, 
-        upload_path.as_str()
-    );
+    // The checksum is part of the uploaded archive, so a download never sees an archive with the checksum of another upload.
+    let checksum_result = digest::write_checksum(archive_file);
 
-    if upload_result.is_err(){
-        return Err("Encountered an error while attempting to upload the zip file. Program will now exit...");
+    if checksum_result.is_err(){
+        eprintln!("Encountered an error while attempting to write the checksum into the archive.");
+        return checksum_result;
+    }
+
+    let copyres = upload_archive(store, archive_file);
+
+    if copyres.is_err(){
+        eprintln!("Encountered an error while attempting to upload the archive to the storage backend.");
+        return copyres;
     }
 
-    println!("All files were uploaded. Program will now exit...");
     return Ok(());
 }
 
-
-/// [`create_webdav_paths`] checks if the required directories exist in a WebDAV server and
-/// creates them if necessary.
+/// [`upload_archive`] uploads the archive to the project directory of the storage backend.
+/// 
+/// Arguments:
+/// 
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the archive is uploaded to.
+/// * `archive_file`: type of [`&Path`], the archive that is uploaded.
 /// 
 /// Returns:
 /// 
-/// The function [`create_webdav_paths()`] returns a [`Result<(), &'static str>`].
-fn create_webdav_paths() -> Result<(), &'static str>{
-    let webdav_addr = envfuncs::get_webdavaddr();
-    let webdav_client = client::Client::init(&envfuncs::get_webdav_user(), &envfuncs::get_webdav_password());
-    let base_folder_structure = webdav_client.list(format!("{}/gitcache", webdav_addr).as_str(), "1");
+/// The function [`upload_archive()`] returns an error if there is a problem.
+fn upload_archive(store: &dyn CacheStore, archive_file: &Path) -> Result<(), CacheError>{
+    let archive_file_name = envfuncs::get_archive_file_name();
+    let upload_result = store.put(archive_file, storage::get_object_path(&archive_file_name).as_str());
 
-    if base_folder_structure.as_ref().is_err() {
-        return Err("Encountered a problem trying to retrieve webdav folder structure.");
+    if upload_result.is_err(){
+        return upload_result;
     }
 
-    let folder_structure = base_folder_structure.unwrap();
-
-    if folder_structure.status() != http::StatusCode::FOUND && folder_structure.status() != http::StatusCode::OK {
-        println!("Could not find root directory of folder structure. Creating it now.");
-        let make_base_dir_res = webdav_client.mkcol(format!("{}/gitcache", webdav_addr).as_str());
+    println!("All files were uploaded. Program will now exit...");
+    return Ok(());
+}
 
-        if make_base_dir_res.is_err() {
-            return Err("Could not make base directory. Please ensure your access credentials are correct.");
-        }
 
-        let make_base_dir_res = make_base_dir_res.unwrap();
-        let make_base_dir_statuscode = make_base_dir_res.status();
+/// [`create_remote_paths`] checks if the base & project directories exist on the storage backend and
+/// creates them if necessary.
+/// 
+/// Arguments:
+/// 
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the directories are created on.
+/// 
+/// Returns:
+/// 
+/// The function [`create_remote_paths()`] returns a [`Result<(), CacheError>`].
+fn create_remote_paths(store: &dyn CacheStore) -> Result<(), CacheError>{
+    for remote_path in [String::from("gitcache"), storage::get_project_path()]{
+        let exists_result = store.exists(remote_path.as_str());
 
-        match make_base_dir_statuscode{
-            http::StatusCode::FORBIDDEN => { return Err("Server returned status FORBIDDEN while creating gitcache folder. Please make sure you have the ability to create and upload files on the webdav server.");}
-            http::StatusCode::ACCEPTED | http::StatusCode::OK  | http::StatusCode::CREATED => { println!("Gitcache folder was created on the server."); }
-            http::StatusCode::CONFLICT => { return Err("Gitcache folder seems to already exist (recieved CONFLICT). But we couldn't read it before. This could be due to insufficient read permissions inside the base folder."); }
-            _ => { return Err("Response contained unknown / unhandled status code.")}
+        if exists_result.is_err(){
+            return Err(exists_result.unwrap_err());
         }
 
-        let result = make_webdav_project_path(webdav_client);
-        if result.is_err(){
-            return result;
+        if exists_result.unwrap(){
+            continue;
         }
 
-        println!("All required Webdav directories were created or already existed");
-        return Ok(());
-    }
-
-    let body_text = folder_structure.text();
-
-    if body_text.is_err(){
-        return Err("Could not read message body when attempting to fetch folder structure.");
-    }
-
-    let body_text = body_text.unwrap();
+        println!("Could not find directory {} on the storage backend. Creating it now.", remote_path);
+        let mkdir_result = store.mkdir(remote_path.as_str());
 
-    if !body_text.contains(format!("<a href=\"{}/\">", envfuncs::get_projectid()).as_str()){
-        println!("Project directory does not exist yet. Creating it now.");
-        return make_webdav_project_path(webdav_client);
+        if mkdir_result.is_err(){
+            return mkdir_result;
+        }
     }
 
-    println!("Cache storage location was checked and alredy existed.");
+    println!("All required directories were created or already existed");
     return Ok(())
 }
 
-/// [`make_webdav_project_path`] creates a project directory on a webdav server and handles
-/// different status codes returned by the server.
+/// [`archive_restore_data`] archives the data.json file and all cached objects, reading them straight from their location,
+/// into `dest_file`.
 /// 
 /// Arguments:
 /// 
-/// * `webdav_client`: type of [`Client`], which is used to make requests to a WebDav Server. 
+/// * `json_data`: type of [`&str`]. The contents of the data.json file, should represent `restore_data`.
+/// * `restore_data`: type of [`&[RestoreData]`]. The objects that are archived.
+/// * `dest_file`: type of [`&Path`]. The file the archive is written to.
+/// * `format`: type of [`enums::ArchiveFormat`]. The format of the archive.
+/// * `compression`: type  of [`structs::Compression`]. The compression algorithm and level used for zip archives.
+/// Tar archives always use the algorithm of their format and only take the level if the algorithm matches.
+/// * `header`: type of [`&[u8]`]. The header written in front of the archive (see [`digest::create_header`]).
 /// 
 /// Returns:
-/// Returns a [`&'static str`] error message if there was an
-/// error during the creation process.
-fn make_webdav_project_path(webdav_client: Client) -> Result<(), &'static str>{
-
-    let make_project_dir_result = webdav_client.mkcol(format!("{}/gitcache/{}", envfuncs::get_webdavaddr(), envfuncs::get_projectid()).as_str());
-
-    if make_project_dir_result.is_err() {
-        return Err("Could not make base directory. Please ensure your access credentials are correct.");
-    }
+/// An error if archiving the objects failed.
+fn archive_restore_data(json_data: &str, restore_data: &[RestoreData], dest_file: &Path, format: enums::ArchiveFormat, compression: structs::Compression, header: &[u8]) -> Result<(), CacheError>{
+    let dest_file = dest_file.to_string_lossy();
+    let excludes = globs::ExcludeRules::from_env();
 
-    let make_project_dir_result = make_project_dir_result.unwrap();
-    let make_project_dir_status_code = make_project_dir_result.status();
+    if format == enums::ArchiveFormat::Zip {
+        let zip_result = zip_restore_data(json_data, restore_data, &dest_file, compression, &excludes, header);
 
-    match make_project_dir_status_code{
-        http::StatusCode::FORBIDDEN => { 
-            return Err("Server returned status FORBIDDEN while trying to create project sub-folder. 
-            Please make sure you have the ability to create and upload files on the webdav server.");
-        }
-        http::StatusCode::ACCEPTED | http::StatusCode::OK | http::StatusCode::CREATED => { 
-            println!("Project sub-folder was created on the server."); 
-        }
-        http::StatusCode::CONFLICT => { 
-            return Err("Project sub-folder seems to already exist (recieved CONFLICT). But we couldn't read it before. 
-            This could be due to insufficient read permissions inside the gitcache folder."); 
-        }
-        _ => { 
-            return Err("Response contained unknown / unhandled status code.")
+        if zip_result.is_err(){
+            return Err(CacheError::zip("Failed to zip up file. This may be because of insufficient permissions or a folder being moved during the zipping operation.", zip_result.unwrap_err()));
         }
+
+        println!("Zipped all files using {} compression and put them into {}", compression.to_string(), dest_file);
+        return Ok(());
     }
 
-    println!("Project directory was created.");
-    return Ok(());
-}
+    let level = if format.compression_type() == Some(compression.compression_type) { compression.level } else { None };
 
-/// [`zip_cache_dir`] zips the contents of the ".cache/" directory recursively and zips it up, 
-/// with the name based on the operating system type and branch name.
-/// 
-/// Arguments:
-/// 
-/// * `os_type`: type  of [`enums::OsType`]. It represents the operating system type.
-/// 
-/// Returns:
-/// An error message if zipping the directory failed.
-fn zip_cache_dir(os_type: enums::OsType) -> Result<(), &'static str>{
-    let branch_name = envfuncs::get_branch_name();
-    let dest_file = envfuncs::get_zip_file_name();
+    if compression.level.is_some() && level.is_none(){
+        println!("Ignoring the compression level of CACHE_COMPRESSION={} since it does not match the archive format {}.", compression.to_string(), format.extension());
+    }
 
-    let zip_result = zip_dir_recursively(".cache/", dest_file.as_str(), zip::CompressionMethod::Stored);
+    let tar_result = tar_restore_data(json_data, restore_data, &dest_file, format, level, &excludes, header);
 
-    if zip_result.is_err(){
-        return Err("Failed to zip up file. This may be because of insufficient permissions or a folder being moved during the zipping operation.");
+    if tar_result.is_err(){
+        return Err(CacheError::archive("Failed to create the tar archive. This may be because of insufficient permissions or a folder being moved during the archiving operation.", tar_result.unwrap_err()));
     }
 
-    println!("Zipped all files and put them into {}-{}.zip", os_type.to_string(), branch_name);
+    println!("Archived all files into {}", dest_file);
     return Ok(());
-}
\ No newline at end of file
+}
@@ -1,2 +1,3 @@
 pub mod structs;
-pub mod enums;
\ No newline at end of file
+pub mod enums;
+pub mod errors;
\ No newline at end of file
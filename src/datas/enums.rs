@@ -1,11 +1,26 @@
+use std::{fs::File, io::Read, ops::RangeInclusive, path::Path};
+
 use serde::{Deserialize, Serialize};
 
+use crate::digest;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CacheType {
     Directory,
     File
 }
 
+/// The directory the restore location of a cached object is stored relative to in the data.json file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathBase {
+    /// The project directory of the job (`CI_PROJECT_DIR`).
+    Project,
+    /// The home directory of the user running the job.
+    Home,
+    /// The location is stored as absolute path, since it is neither inside of the project nor the home directory.
+    Absolute
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OsType {
     Windows,
@@ -43,4 +58,224 @@ impl OsType{
             return OsType::Unknown;
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendType {
+    WebDav,
+    Ftp,
+    S3,
+    FileSystem
+}
+
+impl BackendType {
+    /// Parses the name of a storage backend as used by the `CACHE_BACKEND` enviorement variable.
+    /// 
+    /// Arguments:
+    /// 
+    /// * `name`: type of [`&str`], the name of the backend (e.g. `webdav`, `ftp`, `s3` or `fs`). Casing is ignored.
+    /// 
+    /// Returns:
+    /// returns the matching [`BackendType`] or [`None`] if the backend is unknown.
+    pub fn from_name(name: &str) -> Option<BackendType>{
+        match name.trim().to_lowercase().as_str(){
+            "webdav" | "" => {return Some(BackendType::WebDav)}
+            "ftp" | "ftps" => {return Some(BackendType::Ftp)}
+            "s3" => {return Some(BackendType::S3)}
+            "fs" | "filesystem" => {return Some(BackendType::FileSystem)}
+            _ => {return None}
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreKey {
+    /// Matches the cache with exactly this key.
+    Exact(String),
+    /// Matches any cache whose key starts with this prefix.
+    Prefix(String),
+    /// Matches any cache whose key only differs in the file hashes. Every `*` of the pattern stands for one hex character of a hash.
+    AnyHash(String)
+}
+
+impl RestoreKey {
+    /// [`RestoreKey::matches`] checks if the cache with the key `key` is matched.
+    ///
+    /// Arguments:
+    ///
+    /// * `key`: type of [`&str`], the key of a cache, without the extension of the archive.
+    ///
+    /// Returns:
+    /// A [`bool`] indicating if the cache is matched.
+    pub fn matches(&self, key: &str) -> bool {
+        match self{
+            RestoreKey::Exact(exact_key) => {return key == exact_key}
+            RestoreKey::Prefix(prefix) => {return key.starts_with(prefix.as_str())}
+            RestoreKey::AnyHash(pattern) => {
+                return key.len() == pattern.len() && pattern.bytes().zip(key.bytes())
+                    .all(|(pattern_byte, key_byte)| if pattern_byte == b'*' { key_byte.is_ascii_hexdigit() } else { pattern_byte == key_byte });
+            }
+        }
+    }
+}
+
+impl ToString for RestoreKey {
+    /// converts a [`RestoreKey`] into the notation used by `CACHE_RESTORE_KEYS`.
+    /// 
+    /// Returns:
+    /// [`String`] containing the key, followed by a `*` for prefix matches.
+    fn to_string(&self) -> String {
+        match self{
+            RestoreKey::Exact(key) => {return key.clone()}
+            RestoreKey::Prefix(prefix) => {return format!("{}*", prefix)}
+            RestoreKey::AnyHash(pattern) => {return pattern.clone()}
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionType {
+    Stored,
+    Deflate,
+    Bzip2,
+    Zstd
+}
+
+impl ToString for CompressionType {
+    /// converts a [`CompressionType`] into the name used by the `CACHE_COMPRESSION` enviorement variable.
+    /// 
+    /// Returns:
+    /// [`String`] value depending on the compression type.
+    fn to_string(&self) -> String {
+        match self{
+            CompressionType::Stored => {return String::from("stored")}
+            CompressionType::Deflate => {return String::from("deflate")}
+            CompressionType::Bzip2 => {return String::from("bzip2")}
+            CompressionType::Zstd => {return String::from("zstd")}
+        }
+    }
+}
+
+impl CompressionType {
+    /// Parses the name of a compression algorithm as used by the `CACHE_COMPRESSION` enviorement variable.
+    /// 
+    /// Arguments:
+    /// 
+    /// * `name`: type of [`&str`], the name of the algorithm (e.g. `stored`, `deflate`, `bzip2` or `zstd`). Casing is ignored.
+    /// 
+    /// Returns:
+    /// returns the matching [`CompressionType`] or [`None`] if the algorithm is unknown.
+    pub fn from_name(name: &str) -> Option<CompressionType>{
+        match name.trim().to_lowercase().as_str(){
+            "stored" | "none" => {return Some(CompressionType::Stored)}
+            "deflate" | "deflated" => {return Some(CompressionType::Deflate)}
+            "bzip2" => {return Some(CompressionType::Bzip2)}
+            "zstd" => {return Some(CompressionType::Zstd)}
+            _ => {return None}
+        }
+    }
+
+    /// Returns the range of compression levels the algorithm supports.
+    /// 
+    /// Returns:
+    /// A [`RangeInclusive<i32>`] of valid levels or [`None`] if the algorithm has no levels.
+    pub fn level_range(&self) -> Option<RangeInclusive<i32>>{
+        match self{
+            CompressionType::Stored => {return None}
+            CompressionType::Deflate => {return Some(0..=9)}
+            CompressionType::Bzip2 => {return Some(1..=9)}
+            CompressionType::Zstd => {return Some(1..=22)}
+        }
+    }
+
+    /// Returns the [`zip::CompressionMethod`] used to write zip entries with this algorithm.
+    pub fn to_zip_method(&self) -> zip::CompressionMethod{
+        match self{
+            CompressionType::Stored => {return zip::CompressionMethod::Stored}
+            CompressionType::Deflate => {return zip::CompressionMethod::Deflated}
+            CompressionType::Bzip2 => {return zip::CompressionMethod::Bzip2}
+            CompressionType::Zstd => {return zip::CompressionMethod::Zstd}
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarZst,
+    TarGz
+}
+
+impl ArchiveFormat {
+    /// All archive formats, used to look for caches created with a different format than the current one.
+    pub const ALL: [ArchiveFormat; 3] = [ArchiveFormat::Zip, ArchiveFormat::TarZst, ArchiveFormat::TarGz];
+
+    /// Parses the name of an archive format as used by the `CACHE_ARCHIVE_FORMAT` enviorement variable.
+    /// 
+    /// Arguments:
+    /// 
+    /// * `name`: type of [`&str`], the name of the format (e.g. `zip`, `tar.zst` or `tar.gz`). Casing is ignored.
+    /// 
+    /// Returns:
+    /// returns the matching [`ArchiveFormat`] or [`None`] if the format is unknown.
+    pub fn from_name(name: &str) -> Option<ArchiveFormat>{
+        match name.trim().trim_start_matches('.').to_lowercase().as_str(){
+            "zip" | "" => {return Some(ArchiveFormat::Zip)}
+            "tar.zst" | "tar.zstd" | "tzst" => {return Some(ArchiveFormat::TarZst)}
+            "tar.gz" | "tgz" => {return Some(ArchiveFormat::TarGz)}
+            _ => {return None}
+        }
+    }
+
+    /// Returns the file extension of the format, including the leading dot.
+    pub fn extension(&self) -> &'static str{
+        match self{
+            ArchiveFormat::Zip => {return ".zip"}
+            ArchiveFormat::TarZst => {return ".tar.zst"}
+            ArchiveFormat::TarGz => {return ".tar.gz"}
+        }
+    }
+
+    /// Returns the compression algorithm that is built into the format. Zip archives support multiple algorithms.
+    pub fn compression_type(&self) -> Option<CompressionType>{
+        match self{
+            ArchiveFormat::Zip => {return None}
+            ArchiveFormat::TarZst => {return Some(CompressionType::Zstd)}
+            ArchiveFormat::TarGz => {return Some(CompressionType::Deflate)}
+        }
+    }
+
+    /// Detects the format of an archive by reading the magic bytes at the start of the file, behind the header of the archive.
+    /// 
+    /// Arguments:
+    /// 
+    /// * `path`: type of [`&Path`], the archive to inspect.
+    /// 
+    /// Returns:
+    /// returns the detected [`ArchiveFormat`] or [`None`] if the file could not be read or has an unknown format.
+    pub fn detect(path: &Path) -> Option<ArchiveFormat>{
+        let mut magic_bytes = [0u8; 4];
+        let file = File::open(path);
+
+        if file.is_err(){
+            return None;
+        }
+
+        // The header in front of the archive is skipped, the magic bytes of the format follow it.
+        let mut file = file.unwrap();
+
+        if digest::skip_header(&mut file).is_err() || file.read_exact(&mut magic_bytes).is_err(){
+            return None;
+        }
+
+        match magic_bytes{
+            [0x50, 0x4B, 0x03, 0x04] | [0x50, 0x4B, 0x05, 0x06] => {return Some(ArchiveFormat::Zip)}
+            [0x28, 0xB5, 0x2F, 0xFD] => {return Some(ArchiveFormat::TarZst)}
+            [0x1F, 0x8B, _, _] => {return Some(ArchiveFormat::TarGz)}
+            _ => {return None}
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{error::Error, fmt, io};
+
+/// Exit code of a successful run. A download that does not find a matching cache exits with it as well.
+pub const EXIT_OK: i32 = 0;
+/// Exit code if the operating system is not supported.
+pub const EXIT_UNSUPPORTED_OS: i32 = 1;
+/// Exit code of invalid arguments, enviorement variables or config files.
+pub const EXIT_CONFIG: i32 = 2;
+/// Exit code if the requested cache does not exist on the storage backend.
+pub const EXIT_NOT_FOUND: i32 = 3;
+/// Exit code of errors while accessing the local file system.
+pub const EXIT_IO: i32 = 4;
+/// Exit code of errors while communicating with the storage backend.
+pub const EXIT_STORAGE: i32 = 5;
+/// Exit code of errors while creating or extracting an archive.
+pub const EXIT_ARCHIVE: i32 = 6;
+/// Exit code if the data.json file of the cache could not be written or read.
+pub const EXIT_DATA_FILE: i32 = 7;
+/// Exit code if the cache contents are invalid or were refused.
+pub const EXIT_INVALID: i32 = 8;
+
+/// [`CacheError`] is returned by every operation of the program that can fail.
+/// The error that caused it is kept, so the actual cause (e.g. the http status or the [`io::ErrorKind`]) is printed
+/// together with the description of what we were doing. Every variant maps to a fixed exit code (see [`CacheError::exit_code`]).
+#[derive(Debug)]
+pub enum CacheError {
+    /// The requested cache does not exist on the storage backend.
+    NotFound,
+    /// The arguments, enviorement variables or the config file are invalid.
+    Config(String),
+    /// An operation on the local file system failed.
+    Io { context: String, source: io::Error },
+    /// A request to a http based backend (webdav or S3) failed or was answered with an unexpected status.
+    Http { context: String, status: Option<http::StatusCode>, source: Option<reqwest::Error> },
+    /// An operation on the ftp server failed.
+    Ftp { context: String, source: suppaftp::FtpError },
+    /// Creating or reading a zip archive failed.
+    Zip { context: String, source: zip::result::ZipError },
+    /// Creating or reading a tar archive failed.
+    Archive { context: String, source: io::Error },
+    /// The data.json file could not be serialized or deserialized.
+    Serde { context: String, source: serde_json::Error },
+    /// The cache contents are invalid or were refused, e.g. entries restoring outside of the allowed restore roots.
+    Invalid(String)
+}
+
+impl CacheError {
+    /// Creates a [`CacheError::Config`] error.
+    pub fn config(message: &str) -> CacheError {
+        return CacheError::Config(message.to_string());
+    }
+
+    /// Creates a [`CacheError::Io`] error.
+    pub fn io(context: &str, source: io::Error) -> CacheError {
+        return CacheError::Io { context: context.to_string(), source };
+    }
+
+    /// Creates a [`CacheError::Http`] error for a request that could not be sent or whose response could not be read.
+    pub fn http(context: &str, source: reqwest::Error) -> CacheError {
+        return CacheError::Http { context: context.to_string(), status: source.status(), source: Some(source) };
+    }
+
+    /// Creates a [`CacheError::Http`] error for a response with an unexpected status.
+    pub fn http_status(context: &str, status: http::StatusCode) -> CacheError {
+        return CacheError::Http { context: context.to_string(), status: Some(status), source: None };
+    }
+
+    /// Creates a [`CacheError::Ftp`] error.
+    pub fn ftp(context: &str, source: suppaftp::FtpError) -> CacheError {
+        return CacheError::Ftp { context: context.to_string(), source };
+    }
+
+    /// Creates a [`CacheError::Zip`] error.
+    pub fn zip(context: &str, source: zip::result::ZipError) -> CacheError {
+        return CacheError::Zip { context: context.to_string(), source };
+    }
+
+    /// Creates a [`CacheError::Archive`] error.
+    pub fn archive(context: &str, source: io::Error) -> CacheError {
+        return CacheError::Archive { context: context.to_string(), source };
+    }
+
+    /// Creates a [`CacheError::Serde`] error.
+    pub fn serde(context: &str, source: serde_json::Error) -> CacheError {
+        return CacheError::Serde { context: context.to_string(), source };
+    }
+
+    /// Creates a [`CacheError::Invalid`] error.
+    pub fn invalid(message: &str) -> CacheError {
+        return CacheError::Invalid(message.to_string());
+    }
+
+    /// Checks if the error is a [`CacheError::NotFound`].
+    pub fn is_not_found(&self) -> bool {
+        return matches!(self, CacheError::NotFound);
+    }
+
+    /// [`CacheError::exit_code`] returns the exit code the program ends with because of this error.
+    /// The codes are listed in the `--help` output and don't change between versions.
+    ///
+    /// Returns:
+    /// One of the `EXIT_` constants of this module.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CacheError::NotFound => { return EXIT_NOT_FOUND; }
+            CacheError::Config(_) => { return EXIT_CONFIG; }
+            CacheError::Io { .. } => { return EXIT_IO; }
+            CacheError::Http { .. } | CacheError::Ftp { .. } => { return EXIT_STORAGE; }
+            CacheError::Zip { .. } | CacheError::Archive { .. } => { return EXIT_ARCHIVE; }
+            CacheError::Serde { .. } => { return EXIT_DATA_FILE; }
+            CacheError::Invalid(_) => { return EXIT_INVALID; }
+        }
+    }
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::NotFound => {
+                return write!(f, "Could not find the cache on the storage backend. This probably means you just haven't uploaded the cache yet.
+            If this happens after the cache files were created please contact a system administrator");
+            }
+            CacheError::Config(message) | CacheError::Invalid(message) => { return write!(f, "{}", message); }
+            CacheError::Io { context, source } | CacheError::Archive { context, source } => {
+                return write!(f, "{} Cause: {} ({:?})", context, source, source.kind());
+            }
+            CacheError::Http { context, status, source } => {
+                write!(f, "{}", context)?;
+
+                if let Some(status) = status {
+                    write!(f, " Server returned status {}.", status)?;
+                }
+
+                if let Some(source) = source {
+                    write!(f, " Cause: {}", source)?;
+                }
+
+                return Ok(());
+            }
+            CacheError::Ftp { context, source } => { return write!(f, "{} Cause: {}", context, source); }
+            CacheError::Zip { context, source } => { return write!(f, "{} Cause: {}", context, source); }
+            CacheError::Serde { context, source } => { return write!(f, "{} Cause: {}", context, source); }
+        }
+    }
+}
+
+impl Error for CacheError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CacheError::Io { source, .. } | CacheError::Archive { source, .. } => { return Some(source); }
+            CacheError::Http { source: Some(source), .. } => { return Some(source); }
+            CacheError::Ftp { source, .. } => { return Some(source); }
+            CacheError::Zip { source, .. } => { return Some(source); }
+            CacheError::Serde { source, .. } => { return Some(source); }
+            _ => { return None; }
+        }
+    }
+}
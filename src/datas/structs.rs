@@ -1,4 +1,6 @@
-use crate::datas::enums;
+use std::path::PathBuf;
+use crate::datas::{enums, errors::CacheError};
+use crate::{envfuncs, helpers};
 use serde::{Deserialize, Serialize};
 
 
@@ -6,5 +8,111 @@ use serde::{Deserialize, Serialize};
 pub struct RestoreData {
     pub(crate) restore_obj_name: String,
     pub(crate) cachetype: enums::CacheType,
-    pub(crate) restore_to: String
-}
\ No newline at end of file
+    /// Directory the object is restored into, relative to `base`. Stored as portable path (see [`helpers::to_portable_path`]).
+    pub(crate) restore_to: String,
+    /// The directory `restore_to` is relative to. Missing in data.json files of older versions, their locations are either
+    /// absolute or relative to the project directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) base: Option<enums::PathBase>,
+    /// Name of the object inside of the .cache folder if it differs from `restore_obj_name`,
+    /// e.g. when glob patterns match multiple folders with the same name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) cache_name: Option<String>,
+    /// Suffix of the `cachepath_` or `cachefile_` enviorement variable the object was configured with (e.g. `nodemodules`).
+    /// The object is restored if a variable with the same suffix is set. Missing in data.json files of older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) entry_name: Option<String>,
+    /// Operating system the object was cached on. Objects of other operating systems are only restored if `CACHE_CROSS_OS` is set.
+    /// Missing in data.json files of older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) os: Option<enums::OsType>
+}
+
+impl RestoreData {
+    /// Returns the name of the object inside of the .cache folder.
+    pub fn get_cache_name(&self) -> &str {
+        return self.cache_name.as_deref().unwrap_or(self.restore_obj_name.as_str());
+    }
+
+    /// Returns the directory the object is restored into, resolved against the base directories of this job
+    /// (see [`envfuncs::get_project_dir`] and [`envfuncs::get_home_dir`]). Absolute locations of other operating systems
+    /// are mapped via the pairs of `remap` (see [`helpers::remap_portable_path`]).
+    /// The location stays relative if the home directory of this job is not known or an absolute location could not be mapped.
+    ///
+    /// Arguments:
+    ///
+    /// * `remap`: type of [`&[(String, String)]`], the pairs of `CACHE_PATH_REMAP` (see [`envfuncs::get_path_remap`]). Objects found by this job pass an empty slice.
+    pub fn get_restore_to(&self, remap: &[(String, String)]) -> PathBuf {
+        let (base_dir, restore_to) = match self.base {
+            Some(enums::PathBase::Home) => (envfuncs::get_home_dir().unwrap_or_default(), self.restore_to.clone()),
+            Some(enums::PathBase::Absolute) => (PathBuf::new(), helpers::remap_portable_path(&self.restore_to, remap)),
+            Some(enums::PathBase::Project) | None => (envfuncs::get_project_dir(), self.restore_to.clone())
+        };
+
+        return helpers::normalize_path(&base_dir.join(helpers::from_portable_path(&restore_to)));
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    pub(crate) compression_type: enums::CompressionType,
+    pub(crate) level: Option<i32>
+}
+
+impl Compression {
+    /// Parses a compression setting in the format `<algorithm>` or `<algorithm>:<level>` (e.g. `zstd:3`).
+    /// 
+    /// Arguments:
+    /// 
+    /// * `value`: type of [`&str`], the compression setting to parse.
+    /// 
+    /// Returns:
+    /// The parsed [`Compression`] or an error message if the algorithm is unknown or the level is invalid.
+    pub fn parse(value: &str) -> Result<Compression, CacheError>{
+        let (name, level) = match value.split_once(':') {
+            Some((name, level)) => (name, Some(level)),
+            None => (value, None)
+        };
+
+        let compression_type = enums::CompressionType::from_name(name);
+
+        if compression_type.is_none(){
+            return Err(CacheError::config("Unknown compression algorithm. Supported are: stored, deflate, bzip2, zstd"));
+        }
+
+        let compression_type = compression_type.unwrap();
+
+        if level.is_none(){
+            return Ok(Compression { compression_type, level: None });
+        }
+
+        let level = level.unwrap().trim().parse::<i32>();
+
+        if level.is_err(){
+            return Err(CacheError::config("The compression level has to be a number."));
+        }
+
+        let level = level.unwrap();
+
+        match compression_type.level_range(){
+            None => { return Err(CacheError::config("The selected compression algorithm does not support levels.")); }
+            Some(range) if !range.contains(&level) => {
+                return Err(CacheError::config("The compression level is out of range. Deflate supports 0-9, bzip2 supports 1-9 and zstd supports 1-22."));
+            }
+            Some(_) => { return Ok(Compression { compression_type, level: Some(level) }); }
+        }
+    }
+}
+
+impl ToString for Compression {
+    /// converts a [`Compression`] back into the `<algorithm>:<level>` notation.
+    /// 
+    /// Returns:
+    /// [`String`] describing the compression.
+    fn to_string(&self) -> String {
+        match self.level{
+            Some(level) => {return format!("{}:{}", self.compression_type.to_string(), level)}
+            None => {return self.compression_type.to_string()}
+        }
+    }
+}
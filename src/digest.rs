@@ -0,0 +1,499 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::datas::{errors::CacheError, structs::RestoreData};
+use crate::globs::ExcludeRules;
+use crate::{envfuncs, helpers};
+use crate::storage::{self, CacheStore};
+
+/// File in the work directory (see [`envfuncs::get_work_dir`]) the digest of the downloaded or uploaded cache is written to,
+/// so a later upload of unchanged contents can be skipped without downloading the archive.
+pub const DIGEST_FILE: &str = ".cache_digest";
+
+/// Extension of the file older versions stored next to an archive on the storage backend that contained the digest of the archive contents.
+/// The digest is stored in the header of the archive instead (see [`create_header`]), since it was uploaded after the archive.
+pub const DIGEST_EXTENSION: &str = ".digest";
+
+/// Magic number of a zstd skippable frame. The header written in front of every archive (see [`create_header`]) is such a frame,
+/// so zstd decoders skip it on their own. Readers of the other formats skip it with [`skip_header`].
+const SKIPPABLE_FRAME_MAGIC: &[u8; 4] = b"\x50\x2A\x4D\x18";
+
+/// Name at the start of the header, it tells the header of this tool apart from other skippable frames.
+const HEADER_NAME: &[u8; 8] = b"WCHEADER";
+
+/// Longest header that is read. The headers written by this tool are a lot shorter.
+const MAX_HEADER_LENGTH: u64 = 4096;
+
+/// Field of the header containing the checksum. It is the first field, so it's value is always found at [`CHECKSUM_POSITION`].
+const CHECKSUM_FIELD: &str = "sha256 ";
+
+/// Position of the checksum in the header: behind the start of the skippable frame, the [`HEADER_NAME`] and the [`CHECKSUM_FIELD`].
+const CHECKSUM_POSITION: u64 = (SKIPPABLE_FRAME_MAGIC.len() + 4 + HEADER_NAME.len() + CHECKSUM_FIELD.len()) as u64;
+
+/// Extension of the file older versions stored next to an archive on the storage backend that contained the sha256 checksum of the archive.
+/// The checksum is stored in the header of the archive instead (see [`create_header`]), since downloads could see the new archive with the old checksum.
+pub const CHECKSUM_EXTENSION: &str = ".sha256";
+
+/// Length of a sha256 checksum as hex string.
+const CHECKSUM_LENGTH: usize = 64;
+
+/// [`compute_digest`] calculates a digest over the contents of all cached objects.
+/// Every file, folder and symlink contributes it's path inside of the cache, it's type, it's permissions and the hash of it's
+/// contents (or link target). Modification times are left out, so rebuilding unchanged files does not change the digest.
+/// The entries are visited the same way they are added to the archive (see [`helpers::walk_restore_data`]).
+///
+/// Arguments:
+///
+/// * `json_data`: type of [`&str`], the contents of the data.json file. Changing the restore locations changes the digest as well.
+/// * `restore_data`: type of [`&[RestoreData]`], the objects that are cached.
+/// * `excludes`: type of [`&ExcludeRules`], the exclude rules applied to the cached folders.
+///
+/// Returns:
+/// The digest as hex string or an error if an object could not be read.
+pub fn compute_digest(json_data: &str, restore_data: &[RestoreData], excludes: &ExcludeRules) -> Result<String, CacheError> {
+    let mut hasher = Sha256::new();
+    hasher.update(json_data.as_bytes());
+
+    let walk_result = helpers::walk_restore_data(restore_data, excludes, &mut |path, name, metadata| {
+        hasher.update(format!("{}\t{}\n", name.to_string_lossy(), digest_entry(path, metadata)?).as_bytes());
+        return Ok(());
+    });
+
+    if walk_result.is_err() {
+        return Err(CacheError::io("Could not read a cached object to calculate the digest of the cache.", walk_result.unwrap_err()));
+    }
+
+    return Ok(hex::encode(hasher.finalize()));
+}
+
+/// [`digest_entry`] describes a single file system entry for [`compute_digest`].
+///
+/// Returns:
+/// A line in the format `<type>\t<mode>\t<hash>`.
+fn digest_entry(path: &Path, metadata: &fs::Metadata) -> io::Result<String> {
+    let mode = get_mode(metadata);
+
+    if metadata.file_type().is_symlink() {
+        return Ok(format!("symlink\t{:o}\t{}", mode, fs::read_link(path)?.to_string_lossy()));
+    }
+
+    if metadata.is_dir() {
+        return Ok(format!("dir\t{:o}\t-", mode));
+    }
+
+    return Ok(format!("file\t{:o}\t{}", mode, hash_file(path)?));
+}
+
+/// Calculates the sha256 hash of the contents of a file without reading it into memory.
+///
+/// Returns:
+/// The hash as hex string or an error if the file could not be read.
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    return Ok(hex::encode(hasher.finalize()));
+}
+
+/// Returns the permissions of an entry. On windows only the readonly flag is known.
+fn get_mode(metadata: &fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        return metadata.permissions().mode();
+    }
+
+    #[cfg(windows)]
+    {
+        return metadata.permissions().readonly() as u32;
+    }
+}
+
+/// Returns the path of the digest file older versions stored next to an archive on the storage backend.
+/// Those files are only deleted together with their archive.
+pub fn get_remote_digest_path(archive_file_name: &str) -> String {
+    return storage::get_object_path(format!("{}{}", archive_file_name, DIGEST_EXTENSION).as_str());
+}
+
+/// Returns the path of the [`DIGEST_FILE`] inside of the work directory.
+pub fn get_digest_file() -> Result<PathBuf, CacheError> {
+    return envfuncs::get_work_dir().map(|work_dir| work_dir.join(DIGEST_FILE));
+}
+
+/// [`is_unchanged`] checks if the archive on the storage backend already contains the contents described by `digest`.
+/// The digest recorded by the last download or upload of the archive ([`DIGEST_FILE`]) is compared and the archive itself has to still exist.
+///
+/// Arguments:
+///
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the archive would be uploaded to.
+/// * `archive_file_name`: type of [`&str`], the name of the archive that would be uploaded.
+/// * `digest`: type of [`&str`], the digest of the current contents (see [`compute_digest`]).
+///
+/// Returns:
+/// If the upload can be skipped or an error if the storage backend could not be reached.
+pub fn is_unchanged(store: &dyn CacheStore, archive_file_name: &str, digest: &str) -> Result<bool, CacheError> {
+    let digest_file = get_digest_file();
+
+    if digest_file.is_err() {
+        return Err(digest_file.unwrap_err());
+    }
+
+    let local_digest = fs::read_to_string(digest_file.unwrap()).unwrap_or_default();
+    let recorded_digest = local_digest.trim().strip_prefix(format!("{} ", archive_file_name).as_str()).map(|digest| digest.to_string());
+
+    if recorded_digest.is_none_or(|recorded_digest| recorded_digest.trim() != digest) {
+        return Ok(false);
+    }
+
+    return store.exists(storage::get_object_path(archive_file_name).as_str());
+}
+
+/// Writes the digest of an archive to the [`DIGEST_FILE`]. Failing to do so is only reported, since it only prevents skipping an upload.
+pub fn record_digest(archive_file_name: &str, digest: &str) {
+    let write_result = get_digest_file().and_then(|digest_file| {
+        return fs::write(digest_file, format!("{} {}\n", archive_file_name, digest))
+            .map_err(|write_error| CacheError::io("Could not write the digest file.", write_error));
+    });
+
+    if write_result.is_err() {
+        println!("Could not write the digest of the cache to the work directory. Ignoring since this does not affect the cache itself. Cause: {}",
+            write_result.unwrap_err());
+    }
+}
+
+/// [`ArchiveHeader`] contains the fields of the header written in front of every archive (see [`create_header`]).
+#[derive(Debug)]
+pub struct ArchiveHeader {
+    /// The length of the header in bytes, the archive itself starts after it.
+    pub length: u64,
+    /// The sha256 checksum of the archive behind the header (see [`write_checksum`]).
+    pub checksum: Option<String>,
+    /// The digest of the archive contents (see [`compute_digest`]).
+    pub digest: Option<String>
+}
+
+/// [`create_header`] creates the header that is written in front of an archive. It contains the digest of the archive contents
+/// and room for the checksum of the archive, which is filled in by [`write_checksum`] once the archive was written.
+/// Both are uploaded together with the archive, so a download always gets the digest and checksum of the archive it downloaded.
+/// Since the header is at the start of the archive, a truncated download still contains it and fails the integrity check.
+/// The header is a zstd skippable frame containing the [`HEADER_NAME`], followed by one `<field> <value>` line per field.
+///
+/// Arguments:
+///
+/// * `digest`: type of [`&str`], the digest of the archive contents (see [`compute_digest`]).
+///
+/// Returns:
+/// The bytes of the header.
+pub fn create_header(digest: &str) -> Vec<u8> {
+    let mut content = HEADER_NAME.to_vec();
+    content.extend_from_slice(format!("{}{}\n", CHECKSUM_FIELD, "0".repeat(CHECKSUM_LENGTH)).as_bytes());
+    content.extend_from_slice(format!("digest {}\n", digest).as_bytes());
+
+    let mut header = SKIPPABLE_FRAME_MAGIC.to_vec();
+    header.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    header.extend_from_slice(&content);
+    return header;
+}
+
+/// [`read_header`] reads the header in front of an archive (see [`create_header`]).
+///
+/// Returns:
+/// The header, [`None`] if the archive has no header (archives of older versions) or an error if the archive could not be read.
+pub fn read_header(local_file: &Path) -> io::Result<Option<ArchiveHeader>> {
+    let mut file = File::open(local_file)?;
+    let content_length = read_frame_length(&mut file)?;
+
+    if content_length.is_none_or(|content_length| content_length < HEADER_NAME.len() as u64) {
+        return Ok(None);
+    }
+
+    let content_length = content_length.unwrap();
+    let mut content = Vec::new();
+    file.take(content_length).read_to_end(&mut content)?;
+
+    // Archives that end inside of the header are truncated, even if they end inside of the name.
+    if (content.len() as u64) < content_length && (content.starts_with(HEADER_NAME) || HEADER_NAME.starts_with(&content)) {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The header of the archive is incomplete."));
+    }
+
+    if !content.starts_with(HEADER_NAME) {
+        return Ok(None);
+    }
+
+    let mut header = ArchiveHeader { length: SKIPPABLE_FRAME_MAGIC.len() as u64 + 4 + content_length, checksum: None, digest: None };
+
+    for line in String::from_utf8_lossy(&content[HEADER_NAME.len()..]).lines() {
+        match line.split_once(' ') {
+            Some(("sha256", value)) => { header.checksum = Some(value.to_string()); }
+            Some(("digest", value)) => { header.digest = Some(value.to_string()); }
+            _ => {}
+        }
+    }
+
+    return Ok(Some(header));
+}
+
+/// [`write_checksum`] calculates the sha256 checksum of the archive behind the header and writes it into the header (see [`create_header`]),
+/// so downloads can verify they received the archive that was uploaded (see [`verify_checksum`]).
+///
+/// Arguments:
+///
+/// * `local_file`: type of [`&Path`], the archive before it is uploaded. It has to start with a header.
+///
+/// Returns:
+/// An error if the archive could not be read or written.
+pub fn write_checksum(local_file: &Path) -> Result<(), CacheError> {
+    let header = read_header(local_file);
+
+    if header.is_err() {
+        return Err(CacheError::io("Could not read the header of the archive to write it's checksum.", header.unwrap_err()));
+    }
+
+    let header = header.unwrap();
+
+    if header.as_ref().is_none_or(|header| header.checksum.is_none()) {
+        return Err(CacheError::invalid("The archive has no header to write it's checksum to."));
+    }
+
+    let checksum = hash_archive(local_file, header.unwrap().length);
+
+    if checksum.is_err() {
+        return Err(CacheError::io("Could not read the archive to calculate it's checksum.", checksum.unwrap_err()));
+    }
+
+    let checksum = checksum.unwrap();
+    let write_result = fs::OpenOptions::new().write(true).open(local_file)
+        .and_then(|mut file| file.seek(SeekFrom::Start(CHECKSUM_POSITION)).map(|_| file))
+        .and_then(|mut file| file.write_all(checksum.as_bytes()));
+
+    if write_result.is_err() {
+        return Err(CacheError::io("Could not write the checksum into the header of the archive.", write_result.unwrap_err()));
+    }
+
+    return Ok(());
+}
+
+/// [`verify_checksum`] compares the sha256 checksum of a downloaded archive with the checksum in it's header (see [`write_checksum`]).
+/// If they don't match, or the archive ends inside of it's header, the downloaded archive is deleted, since it is truncated,
+/// corrupted or not the archive that was uploaded. Archives uploaded by older versions have no header, those are only reported and not verified.
+///
+/// Arguments:
+///
+/// * `local_file`: type of [`&Path`], the downloaded archive.
+/// * `key`: type of [`&str`], the key of the downloaded cache. It is part of the error message.
+///
+/// Returns:
+/// A [`CacheError::Invalid`] error if the integrity check failed, or an error if the archive could not be read.
+pub fn verify_checksum(local_file: &Path, key: &str) -> Result<(), CacheError> {
+    let header = read_header(local_file);
+
+    if header.as_ref().is_err_and(|header_error| header_error.kind() == io::ErrorKind::UnexpectedEof) {
+        let _ = fs::remove_file(local_file);
+        return Err(CacheError::Invalid(format!("The integrity check of the cache {} failed, the downloaded archive was discarded.
+        The archive ends inside of it's header, the download is truncated.", key)));
+    }
+
+    if header.is_err() {
+        return Err(CacheError::io("Could not read the header of the downloaded archive.", header.unwrap_err()));
+    }
+
+    let header = match header.unwrap() {
+        Some(header) => header,
+        None => {
+            println!("The archive of the cache {} has no checksum. Skipping the integrity check.", key);
+            return Ok(());
+        }
+    };
+
+    let actual_checksum = hash_archive(local_file, header.length);
+
+    if actual_checksum.is_err() {
+        return Err(CacheError::io("Could not read the downloaded archive to calculate it's checksum.", actual_checksum.unwrap_err()));
+    }
+
+    let actual_checksum = actual_checksum.unwrap();
+    let expected_checksum = header.checksum.unwrap_or_default();
+
+    if !expected_checksum.eq_ignore_ascii_case(actual_checksum.as_str()) {
+        let _ = fs::remove_file(local_file);
+        return Err(CacheError::Invalid(format!("The integrity check of the cache {} failed, the downloaded archive was discarded.
+        Expected the sha256 checksum {} but the downloaded archive has {}. The archive is truncated, corrupted or was tampered with.",
+            key, expected_checksum, actual_checksum)));
+    }
+
+    println!("Verified the sha256 checksum of the downloaded archive.");
+    return Ok(());
+}
+
+/// Calculates the sha256 checksum of the archive behind the header of `local_file`, which is `header_length` bytes long.
+fn hash_archive(local_file: &Path, header_length: u64) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let hash_result = File::open(local_file)
+        .and_then(|mut file| file.seek(SeekFrom::Start(header_length)).map(|_| file))
+        .and_then(|mut file| io::copy(&mut file, &mut hasher));
+
+    if hash_result.is_err() {
+        return Err(hash_result.unwrap_err());
+    }
+
+    return Ok(hex::encode(hasher.finalize()));
+}
+
+/// [`skip_header`] moves `file` to the start of the archive, behind the header written by [`create_header`].
+/// Files without header are moved to their start.
+///
+/// Returns:
+/// An error if the file could not be read.
+pub fn skip_header(file: &mut File) -> io::Result<()> {
+    let content_length = read_frame_length(file)?;
+
+    match content_length {
+        Some(content_length) => { file.seek(SeekFrom::Start(SKIPPABLE_FRAME_MAGIC.len() as u64 + 4 + content_length))?; }
+        None => { file.seek(SeekFrom::Start(0))?; }
+    }
+
+    return Ok(());
+}
+
+/// Reads the length of the zstd skippable frame at the start of `file`.
+///
+/// Returns:
+/// The length of the content of the frame, [`None`] if the file does not start with a skippable frame the header could be.
+fn read_frame_length(file: &mut File) -> io::Result<Option<u64>> {
+    let mut frame_start = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.take(SKIPPABLE_FRAME_MAGIC.len() as u64 + 4).read_to_end(&mut frame_start)?;
+
+    if frame_start.len() < SKIPPABLE_FRAME_MAGIC.len() + 4 || !frame_start.starts_with(SKIPPABLE_FRAME_MAGIC) {
+        return Ok(None);
+    }
+
+    let content_length = u32::from_le_bytes([frame_start[4], frame_start[5], frame_start[6], frame_start[7]]) as u64;
+
+    if content_length > MAX_HEADER_LENGTH {
+        return Ok(None);
+    }
+
+    return Ok(Some(content_length));
+}
+
+/// Returns the path of the checksum file stored next to an archive by older versions. Those files are only deleted together with their archive.
+pub fn get_remote_checksum_path(archive_file_name: &str) -> String {
+    return storage::get_object_path(format!("{}{}", archive_file_name, CHECKSUM_EXTENSION).as_str());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{create_header, read_header, verify_checksum, write_checksum};
+    use crate::datas::enums::ArchiveFormat;
+    use crate::testutils::{self, Entry, TestEnv};
+
+    /// Writes an archive in `format` containing a single file.
+    fn write_archive(test_env: &TestEnv, format: ArchiveFormat) -> PathBuf {
+        let archive = test_env.path().join(format!("cache{}", format.extension()));
+        let entries = [Entry::File("data.json", "[]"), Entry::File("deps/a.txt", "a")];
+
+        testutils::write_archive(&archive, format, &entries);
+        return archive;
+    }
+
+    #[test]
+    fn archives_with_header_are_detected_and_extracted() {
+        for format in ArchiveFormat::ALL {
+            let test_env = TestEnv::new();
+            let archive = write_archive(&test_env, format);
+            assert!(read_header(&archive).unwrap().is_none());
+
+            let mut contents = create_header("0123abcd");
+            contents.extend(fs::read(&archive).unwrap());
+            fs::write(&archive, contents).unwrap();
+
+            assert_eq!(read_header(&archive).unwrap().unwrap().digest, Some(String::from("0123abcd")));
+            assert_eq!(ArchiveFormat::detect(&archive), Some(format));
+
+            let into = test_env.path().join("extracted");
+            let extract_result = testutils::extract_archive(&archive, format, &into, &test_env.path());
+
+            assert!(extract_result.is_ok(), "{:?}: {}", format, extract_result.unwrap_err());
+            assert_eq!(testutils::read(&into.join("deps/a.txt")), "a");
+        }
+    }
+
+    /// Writes an archive in `format` with a header and it's checksum, like the archives that are uploaded.
+    fn write_uploaded_archive(test_env: &TestEnv, format: ArchiveFormat) -> PathBuf {
+        let archive = write_archive(test_env, format);
+        let mut contents = create_header("0123abcd");
+        contents.extend(fs::read(&archive).unwrap());
+        fs::write(&archive, contents).unwrap();
+
+        write_checksum(&archive).unwrap();
+        return archive;
+    }
+
+    #[test]
+    fn verify_accepts_archives_with_matching_checksum() {
+        for format in ArchiveFormat::ALL {
+            let test_env = TestEnv::new();
+            let archive = write_uploaded_archive(&test_env, format);
+            let contents = fs::read(&archive).unwrap();
+            let header = read_header(&archive).unwrap().unwrap();
+
+            assert!(header.checksum.is_some_and(|checksum| checksum.len() == 64 && checksum != "0".repeat(64)));
+            assert_eq!(header.digest, Some(String::from("0123abcd")));
+
+            let verify_result = verify_checksum(&archive, "key");
+
+            assert!(verify_result.is_ok(), "{:?}: {}", format, verify_result.unwrap_err());
+            assert_eq!(fs::read(&archive).unwrap(), contents);
+        }
+    }
+
+    #[test]
+    fn verify_discards_changed_archives_and_accepts_archives_without_header() {
+        let test_env = TestEnv::new();
+        let archive = write_archive(&test_env, ArchiveFormat::TarZst);
+
+        assert!(verify_checksum(&archive, "key").is_ok());
+        assert!(archive.exists());
+
+        let archive = write_uploaded_archive(&test_env, ArchiveFormat::TarZst);
+        let mut contents = fs::read(&archive).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 0xff;
+        fs::write(&archive, contents).unwrap();
+
+        let verify_result = verify_checksum(&archive, "key");
+
+        assert!(verify_result.is_err());
+        let verify_error = verify_result.unwrap_err();
+        assert_eq!(verify_error.exit_code(), 8);
+        assert!(verify_error.to_string().contains("cache key failed"), "{}", verify_error);
+        assert!(!archive.exists());
+    }
+
+    #[test]
+    fn verify_discards_truncated_archives() {
+        for format in ArchiveFormat::ALL {
+            let test_env = TestEnv::new();
+            let contents = fs::read(write_uploaded_archive(&test_env, format)).unwrap();
+            let header_length = read_header(&test_env.path().join(format!("cache{}", format.extension()))).unwrap().unwrap().length as usize;
+
+            // Cuts off the end of the archive, the whole archive and parts of the header.
+            for cut in [1, 64, 80, contents.len() / 2, contents.len() - header_length, contents.len() - 20, contents.len() - 10] {
+                let archive = test_env.path().join("truncated");
+                fs::write(&archive, &contents[..contents.len() - cut]).unwrap();
+
+                let verify_result = verify_checksum(&archive, "key");
+
+                assert!(verify_result.is_err_and(|error| error.exit_code() == 8), "{:?}: cut {} bytes", format, cut);
+                assert!(!archive.exists(), "{:?}: cut {} bytes", format, cut);
+            }
+        }
+    }
+}
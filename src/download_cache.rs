@@ -1,157 +1,540 @@
-use std::{env, fs::{self}, io::Write, path::Path};
-use rustydav::client;
-use crate::{envfuncs, datas::enums, zip};
+use std::{env, fs::{self}, path::{Path, PathBuf}};
+use crate::{cache_key, datas::{enums, errors::CacheError}, digest, envfuncs, helpers, restore_cache, tar, zip};
+use crate::storage::{self, CacheStore};
 
+/// File the key of the restored cache is written to.
+pub const MATCHED_KEY_FILE: &str = ".cache_key";
 
-/// Downloads the correct zip file depending on [`os_type`] reached in and unzips it's contents to the .cache folder.
+
+/// Downloads the archive of the first matching cache key and extracts it's contents to the .cache folder.
+/// If `restore` is set the contents are restored straight to their locations instead (see [`restore_cache::restore_archive`]),
+/// the archive is then downloaded into the work directory (see [`envfuncs::get_work_dir`]) and no .cache folder is created.
+/// The key that was restored is written to the [`MATCHED_KEY_FILE`] so later steps can read it.
+/// The status of the download is written to the dotenv file (see [`write_dotenv`]), whether a cache was found or not.
 /// 
 /// Arguments:
 /// 
-/// * `os_type`: type of [`enums::OsType`]. Used to determine the current operating system that's being used
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the cache is downloaded from.
+/// * `restore`: type of [`bool`], if the contents are restored instead of being extracted to the .cache folder.
 /// 
 /// Returns:
-/// Returns an error code if there was a problem, indicating location of error.
-pub fn main(os_type: enums::OsType) -> Result<(), i32>{
+/// Returns the error if there was a problem. [`CacheError::NotFound`] if none of the keys matched a cache.
+pub fn main(store: &dyn CacheStore, restore: bool) -> Result<(), CacheError>{
     println!("Welcome to the caching download tool. 
     This tool will download your cache to the .cache folder.");
     println!("Current directory is: {}", env::current_dir().unwrap().to_str().unwrap());
 
-    let branch_name = envfuncs::get_branch_name();
-    let download_file_result = download_files_from_webdav(os_type, branch_name);
+    let download_file_result = download_files(store, restore);
 
-    if download_file_result.as_deref().is_err(){
-        eprintln!("Encountered an error / warning while downloading / creating file. \nError was:{}", download_file_result.clone().unwrap_err());
+    if download_file_result.is_err(){
+        eprintln!("Encountered an error / warning while downloading / creating file.");
+        write_dotenv(None, 0);
+        return Err(download_file_result.unwrap_err());
+    }
+    
+    let (file_name, matched_key) = download_file_result.unwrap();
+    let archive_file_name = Path::new(&file_name).file_name().unwrap_or_default().to_string_lossy().to_string();
+    let archive_size = fs::metadata(&file_name).map(|metadata| metadata.len()).unwrap_or_default();
+    println!("Restoring cache from key: {}", matched_key);
 
-        //Return error code 0 here if the message contains "Could not find the file on the server." since we want to make sure that we don't exit the program just because of that error
-        if download_file_result.unwrap_err().contains("Could not find the file on the server.") {
-            return Err(0);
-        }
+    if fs::write(MATCHED_KEY_FILE, matched_key.as_bytes()).is_err(){
+        println!("Could not write the restored key to {}. Ignoring since this does not affect the cache itself.", MATCHED_KEY_FILE);
+    }
+
+    record_archive_digest(Path::new(&file_name), archive_file_name.as_str());
 
+    let extract_result = if restore { restore_and_del(file_name) } else { extract_and_del(file_name) };
 
-        return Err(22);
+    if extract_result.is_err(){
+        eprintln!("Encountered an error / warning while trying to extract the archive.");
+        write_dotenv(None, 0);
+        return Err(extract_result.unwrap_err());
     }
-    
-    let file_name = download_file_result.unwrap();
-    let unzip_result = unzip_and_del(file_name);
 
-    if unzip_result.is_err(){
-        eprintln!("Encountered an error / warning while trying to unzip file. \nError was:{}", unzip_result.unwrap_err());
-        return Err(23);
+    if restore {
+        println!("Restored the cache {} to the locations it was created from.", matched_key);
     }
 
+    write_dotenv(Some(matched_key.as_str()), archive_size);
     return Ok(());
 }
 
-/// [`download_files_from_webdav`] downloads a zip file from a webdav server. Zip file is determined based on [`os_type`]
+/// [`write_dotenv`] writes the status of the download to the file returned by [`envfuncs::get_dotenv_file`].
+/// The file uses the dotenv format of gitlab (`artifacts:reports:dotenv`), so later steps and jobs can branch on
+/// `CACHE_HIT`, `CACHE_KEY_MATCHED` and `CACHE_SIZE`. Failing to write the file is only reported.
 /// 
 /// Arguments:
 /// 
-/// * `os_type`: type of [`enums::OsType`], used to determine the current OS you're using to know which zip file to download
-/// * `branch_name`: type of [`String`] that represents the name of the branch in a Git repository. It is used to construct the path for the cache to be stored.
+/// * `matched_key`: type of [`Option<&str>`], the key of the restored cache. [`None`] if no cache was restored.
+/// * `archive_size`: type of [`u64`], the size of the downloaded archive in bytes.
+fn write_dotenv(matched_key: Option<&str>, archive_size: u64){
+    // Only the exact key of this job counts as a hit, restoring any other key is a fallback.
+    let cache_hit = matched_key.is_some_and(|matched_key| matched_key == cache_key::get_cache_key());
+    let dotenv = format!("CACHE_HIT={}\nCACHE_KEY_MATCHED={}\nCACHE_SIZE={}\n", cache_hit, matched_key.unwrap_or_default(), archive_size);
+    let dotenv_file = envfuncs::get_dotenv_file();
+
+    if fs::write(&dotenv_file, dotenv).is_err(){
+        println!("Could not write the download status to {}. Ignoring since this does not affect the cache itself.", dotenv_file);
+    }
+}
+
+/// [`record_archive_digest`] reads the digest from the header of the downloaded archive and writes it to the [`digest::DIGEST_FILE`],
+/// so uploading the same contents again can be skipped without downloading the archive.
+/// Archives without a digest and errors are only reported, since they only prevent skipping the upload.
 /// 
-/// Returns:
+/// Arguments:
 /// 
-/// [`download_files_from_webdav`] if successful returns a [`String`] representing the file name of the downloaded file, 
-/// and if there was an error returns [`&'static str`] representing the error message.
-fn download_files_from_webdav(os_type: enums::OsType, branch_name: String) -> Result<String, &'static str>{
-    if Path::new(".cache").exists(){
-        return Err("We already found a folder named .cache. Aborting download since this may cause issues / conflics. Please make sure you don't have a project in your repository with that name.");
+/// * `local_file`: type of [`&Path`], the downloaded archive.
+/// * `archive_file_name`: type of [`&str`], the name of the downloaded archive on the storage backend.
+fn record_archive_digest(local_file: &Path, archive_file_name: &str){
+    let header_result = digest::read_header(local_file);
+
+    if header_result.is_err(){
+        println!("Could not read the digest of the archive. The next upload will not be skipped. Cause: {}", header_result.unwrap_err());
+        return;
     }
 
-    let failed_create_dir = fs::create_dir_all(".cache").is_err();
-
-    if failed_create_dir {
-        return Err("Could not create cache dir at .cache. Please ensure we have write permissions in the current directory you work in and that the folder doesn't already exist. This is an unrecoverable error aborting program.");
+    match header_result.unwrap().and_then(|header| header.digest){
+        Some(archive_digest) => { digest::record_digest(archive_file_name, archive_digest.as_str()); }
+        None => { println!("The archive contains no digest. The next upload will not be skipped."); }
     }
+}
 
-    let webdav_address = envfuncs::get_webdavaddr();
-    let webdav_client = client::Client::init(&envfuncs::get_webdav_user(), &envfuncs::get_webdav_password());
-    let file_name = format!{"{}-{}.zip", os_type.to_string(), envfuncs::get_branch_name()};
-    let download_result = webdav_client.get(format!("{}/gitcache/{}/{}", webdav_address, envfuncs::get_projectid(), file_name).as_str());
+/// [`download_files`] downloads an archive from the storage backend. The keys returned by [`cache_key::get_restore_keys`]
+/// are tried in order and the first cache that exists and passes the integrity check (see [`digest::verify_checksum`]) is downloaded.
+/// For exact keys the configured archive format is tried first, followed by the other formats so caches created with a different format
+/// can still be restored. For prefix keys the matching caches are tried from the newest to the oldest (see [`find_matches`]).
+/// A cache that can't be downloaded or fails the integrity check doesn't end the key, the next candidate is tried and the error is only returned if none of them succeeded.
+/// 
+/// Arguments:
+/// 
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the archive is downloaded from.
+/// * `restore`: type of [`bool`], if the archive is downloaded into the work directory instead of the .cache folder.
+/// 
+/// Returns:
+/// 
+/// [`download_files`] if successful returns a tuple of [`String`]s representing the file name of the downloaded file and the key that matched, 
+/// and if there was an error returns the [`CacheError`].
+fn download_files(store: &dyn CacheStore, restore: bool) -> Result<(String, String), CacheError>{
+    // Restored archives are extracted straight to the cached locations, so they are downloaded outside of the project.
+    let download_dir = if restore { envfuncs::get_work_dir() } else { Ok(PathBuf::from(".cache")) };
+
+    if download_dir.is_err() {
+        eprintln!("Encountered an error while creating the work directory the archive is downloaded to.");
+        return Err(download_dir.unwrap_err());
+    }
 
+    let download_dir = download_dir.unwrap();
 
-    if download_result.as_ref().is_err() {
-        return Err("Encountered an error downloading cache file from server.");
+    if !restore && Path::new(".cache").exists(){
+        return Err(CacheError::invalid("We already found a folder named .cache. Aborting download since this may cause issues / conflics. Please make sure you don't have a project in your repository with that name."));
     }
 
-    let download_result = download_result.unwrap();
-    let download_result_status_code = download_result.status();
+    if !restore {
+        let create_dir_result = fs::create_dir_all(".cache");
 
-    match download_result_status_code{
-        http::StatusCode::FORBIDDEN => { 
-            return Err("Server returned status FORBIDDEN while trying to create project sub-folder. 
-                        Please make sure you have the ability to create and upload files on the webdav server.");
+        if create_dir_result.is_err() {
+            return Err(CacheError::io("Could not create cache dir at .cache. Please ensure we have write permissions in the current directory you work in and that the folder doesn't already exist. This is an unrecoverable error aborting program.", create_dir_result.unwrap_err()));
         }
-        http::StatusCode::NOT_FOUND => { 
-            return Err("Could not find the file on the server. Exiting here with non 0 exit code since this probably means you just haven't uploaded the cache yet. 
-            If this happens after the cache files were created please contact a system administrator");
-        }
-        http::StatusCode::ACCEPTED | http::StatusCode::OK | http::StatusCode::CREATED => { 
-            println!("Project sub-folder was created on the server."); 
+    }
+
+    let configured_format = envfuncs::get_archive_format();
+    let mut formats = vec![configured_format];
+    formats.extend(enums::ArchiveFormat::ALL.iter().filter(|format| **format != configured_format));
+
+    for restore_key in cache_key::get_restore_keys(){
+        let archive_file_names = match &restore_key {
+            enums::RestoreKey::Exact(key) => formats.iter().map(|format| format!("{}{}", key, format.extension())).collect(),
+            enums::RestoreKey::Prefix(_) | enums::RestoreKey::AnyHash(_) => {
+                let key_matches = find_matches(store, &restore_key);
+
+                if key_matches.is_err(){
+                    return Err(key_matches.unwrap_err());
+                }
+
+                key_matches.unwrap()
+            }
+        };
+        let mut candidate_error: Option<CacheError> = None;
+
+        for archive_file_name in archive_file_names{
+            let file_name = download_dir.join(archive_file_name.as_str()).to_string_lossy().to_string();
+            let download_result = store.get(storage::get_object_path(&archive_file_name).as_str(), Path::new(&file_name));
+
+            if download_result.as_ref().is_err_and(|error| error.is_not_found()) {
+                continue;
+            }
+
+            if download_result.is_err() {
+                let download_error = download_result.unwrap_err();
+                println!("Could not download the cache {}, trying the next one. Cause: {}", archive_file_name, download_error);
+                candidate_error = Some(download_error);
+                continue;
+            }
+
+            let verify_result = digest::verify_checksum(Path::new(&file_name), archive_file_name.as_str());
+
+            if verify_result.is_err() {
+                let verify_error = verify_result.unwrap_err();
+                eprintln!("{} Trying the next cache matching the key.", verify_error);
+                candidate_error = Some(verify_error);
+                continue;
+            }
+
+            let matched_key = strip_archive_extension(archive_file_name.as_str()).unwrap_or(archive_file_name.as_str()).to_string();
+            return Ok((file_name, matched_key));
         }
-        http::StatusCode::CONFLICT => { 
-            return Err("Project sub-folder seems to already exist (recieved CONFLICT). 
-                        But we couldn't read it before. This could be due to insufficient read permissions inside the gitcache folder.")
-        } 
-        _ => { 
-            return Err("Response contained unknown / unhandled status code.")
+
+        if let Some(candidate_error) = candidate_error {
+            return Err(candidate_error);
         }
+
+        println!("Could not find a cache matching the key {}. Trying the next key.", restore_key.to_string());
     }
 
-    let file_name = format!(".cache/{}-{}.zip", os_type.to_string(), branch_name);
-    let file = fs::OpenOptions::new()
-    .create(true)
-    .write(true)
-    .open(file_name.clone());
+    return Err(CacheError::NotFound);
+}
 
+/// [`find_matches`] searches the project directory of the storage backend for caches whose key is matched by `restore_key`.
+/// The caches are sorted by the time they were last modified on the storage backend, the newest one first.
+/// Caches without a modification time come last, caches modified at the same time are sorted by their name.
+/// 
+/// Arguments:
+/// 
+/// * `store`: type of [`&dyn CacheStore`], the storage backend to search.
+/// * `restore_key`: type of [`&enums::RestoreKey`], the key the caches have to match.
+/// 
+/// Returns:
+/// 
+/// The file names of the matching caches, empty if no cache matched, or an error if the caches could not be listed.
+fn find_matches(store: &dyn CacheStore, restore_key: &enums::RestoreKey) -> Result<Vec<String>, CacheError>{
+    let list_result = store.list(storage::get_project_path().as_str());
 
-    if file.is_err(){
-        return Err("Encountered an error while attempting to create file. This maybe due to insufficient permissions");
+    if list_result.as_ref().is_err_and(|error| error.is_not_found()) {
+        return Ok(Vec::new());
     }
 
-    let mut file = file.unwrap();
-    let file_buffer = download_result.bytes();
-
-    if file_buffer.is_err(){
-        return Err("Could not unwrap bytes of file. This may be because the file is empty or did not download properly.")
+    if list_result.is_err(){
+        return Err(list_result.unwrap_err());
     }
 
-    let file_buffer = file_buffer.unwrap();
-    let file_write_result = file.write_all(file_buffer.as_ref());
+    let mut matches: Vec<storage::StoredObject> = list_result.unwrap()
+        .into_iter()
+        .filter(|object| strip_archive_extension(object.name.as_str()).is_some_and(|key| restore_key.matches(key)))
+        .collect();
+    matches.sort_by(|first, second| second.modified.cmp(&first.modified).then_with(|| second.name.cmp(&first.name)));
 
-    if file_write_result.is_err(){
-        return Err("Encountered an error while writing the downloaded bytes to the file. This may indicate insufficient permissions to edit files.");
-    }
+    return Ok(matches.into_iter().map(|object| object.name).collect());
+}
 
-    return Ok(file_name);
+/// Removes the extension of any supported archive format from `file_name`.
+/// 
+/// Returns:
+/// 
+/// The cache key contained in the file name or [`None`] if the file is not an archive.
+fn strip_archive_extension(file_name: &str) -> Option<&str>{
+    return enums::ArchiveFormat::ALL.iter().find_map(|format| file_name.strip_suffix(format.extension()));
 }
 
 
-/// `unzip_and_del` unzips a file, moves it to a cache folder, and then deletes the original zip file.
+/// `extract_and_del` extracts an archive into the cache folder, and then deletes the original archive.
+/// The format of the archive is detected from it's first bytes, independent of the file extension.
 /// 
 /// Arguments:
 /// 
-/// * `file_name`: type of [`String`], representing the name of the file to be unzipped and deleted.
+/// * `file_name`: type of [`String`], representing the name of the file to be extracted and deleted.
 /// 
 /// Returns:
 /// 
-/// The function [`unzip_and_del`] returns an error message if there was a problem.
-fn unzip_and_del(file_name: String) -> Result<(), &'static str>{
-    let unzip_has_error = zip::unzip_file(file_name.clone()).is_err();
+/// The function [`extract_and_del`] returns an error if there was a problem.
+fn extract_and_del(file_name: String) -> Result<(), CacheError>{
+    let format = enums::ArchiveFormat::detect(Path::new(&file_name));
+
+    if format.is_none(){
+        return Err(CacheError::invalid("Could not detect the format of the downloaded archive. The file may be corrupted or was not created by this tool."));
+    }
+
+    let format = format.unwrap();
+    let cache_dir = fs::canonicalize(".cache");
 
-    if unzip_has_error {
-        return Err("Encountered an error while attempting to unzip the directory.");
+    if cache_dir.is_err(){
+        return Err(CacheError::io("Could not find the .cache folder to extract the archive into.", cache_dir.unwrap_err()));
     }
 
-    println!("Unzipped file to .cache folder.");
+    let cache_dir = [cache_dir.unwrap()];
+    let destination = |name: &Path| Some(cache_dir[0].join(name));
+    let target = helpers::ExtractTarget { destination: &destination, roots: &cache_dir, check_links: false };
+    let extract_result = match format {
+        enums::ArchiveFormat::Zip => zip::unzip_file(Path::new(&file_name), &target),
+        _ => tar::untar_file(Path::new(&file_name), format, &target)
+    };
+
+    if extract_result.is_err() {
+        return extract_result;
+    }
+
+    println!("Extracted {} archive to .cache folder.", format.extension());
 
     let remove_cache_has_error = fs::remove_file(file_name).is_err();
 
     if remove_cache_has_error {
-        println!("Encountered an error while attempting to delete the cache zip file. 
+        println!("Encountered an error while attempting to delete the cache archive. 
         Ignoring but this should not happen and maybe due to a permission error");
     }
 
-    println!("Deleted cache zip file.");
+    println!("Deleted cache archive.");
     return Ok(());
-}
\ No newline at end of file
+}
+
+/// [`restore_and_del`] restores the contents of an archive straight to their locations and then deletes the archive.
+/// The archive is deleted even if restoring failed, since it is not needed anymore.
+/// 
+/// Arguments:
+/// 
+/// * `file_name`: type of [`String`], representing the name of the file to be restored and deleted.
+/// 
+/// Returns:
+/// 
+/// The function [`restore_and_del`] returns an error if there was a problem.
+fn restore_and_del(file_name: String) -> Result<(), CacheError>{
+    let restore_result = restore_cache::restore_archive(Path::new(&file_name));
+
+    if fs::remove_file(&file_name).is_err() {
+        println!("Encountered an error while attempting to delete the cache archive. 
+        Ignoring but this should not happen and maybe due to a permission error");
+    }
+
+    return restore_result;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use crate::storage::{self, filesystem::FileSystemStore, memory::MemoryStore, CacheStore};
+    use crate::testutils::{self, TestEnv};
+    use crate::{cache_key, create_cache, digest, envfuncs, restore_cache};
+
+    /// Caches the folder `deps` of the test directory to `store` and removes it afterwards.
+    fn save_deps(test_env: &TestEnv, store: &MemoryStore) {
+        test_env.write("deps/lib/a.txt", "a");
+        test_env.write("deps/b.txt", "b");
+        test_env.set("cachepath_deps", "deps");
+
+        let save_result = create_cache::main(store);
+        assert!(save_result.is_ok(), "{}", save_result.unwrap_err());
+        fs::remove_dir_all(test_env.path().join("deps")).unwrap();
+    }
+
+    #[test]
+    fn save_creates_project_directory_and_archive() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        save_deps(&test_env, &store);
+
+        assert!(store.exists("gitcache/42").unwrap());
+        assert!(store.object(storage::get_object_path(format!("{}.zip", cache_key::get_cache_key()).as_str()).as_str()).is_some());
+        assert!(store.object(storage::get_object_path(format!("{}.zip.sha256", cache_key::get_cache_key()).as_str()).as_str()).is_none());
+    }
+
+    #[test]
+    fn save_leaves_no_archive_behind() {
+        let test_env = TestEnv::new();
+        let archive_file_name = format!("{}.zip", cache_key::get_cache_key());
+        save_deps(&test_env, &MemoryStore::new());
+
+        assert!(!test_env.path().join(archive_file_name.as_str()).exists());
+        assert!(!envfuncs::get_work_dir().unwrap().join(archive_file_name.as_str()).exists());
+
+        // A directory in place of the archive makes the upload fail after the archive was written.
+        test_env.write(format!("store/gitcache/42/{}/blocked", archive_file_name).as_str(), "");
+        test_env.set("FSROOT", test_env.path().join("store").to_str().unwrap());
+        test_env.write("deps/b.txt", "b");
+        test_env.set("cachepath_deps", "deps");
+
+        assert!(create_cache::main(&FileSystemStore::from_env()).is_err());
+        assert!(!test_env.path().join(archive_file_name.as_str()).exists());
+        assert!(!envfuncs::get_work_dir().unwrap().join(archive_file_name.as_str()).exists());
+    }
+
+    #[test]
+    fn download_restore_restores_in_one_pass() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        save_deps(&test_env, &store);
+
+        let download_result = super::main(&store, true);
+
+        assert!(download_result.is_ok(), "{}", download_result.unwrap_err());
+        assert_eq!(testutils::read(&test_env.path().join("deps/lib/a.txt")), "a");
+        assert_eq!(testutils::read(&test_env.path().join("deps/b.txt")), "b");
+        assert!(!Path::new(".cache").exists());
+        assert!(testutils::read(Path::new("cache.env")).contains("CACHE_HIT=true"));
+
+        // The archive is neither left in the project nor in the work directory it was downloaded to.
+        for dir in [test_env.path(), envfuncs::get_work_dir().unwrap()] {
+            assert!(fs::read_dir(dir).unwrap().all(|entry| !entry.unwrap().file_name().to_string_lossy().ends_with(".zip")));
+        }
+    }
+
+    #[test]
+    fn upload_of_downloaded_contents_is_skipped() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        save_deps(&test_env, &store);
+        let archive_path = storage::get_object_path(format!("{}.zip", cache_key::get_cache_key()).as_str());
+        let modified = || store.list(storage::get_project_path().as_str()).unwrap()[0].modified;
+        let uploaded = modified();
+
+        // The digest is read from the header of the downloaded archive and recorded outside of the project directory.
+        fs::remove_dir_all(envfuncs::get_work_dir().unwrap()).unwrap();
+        assert!(super::main(&store, true).is_ok());
+        assert!(!test_env.path().join(digest::DIGEST_FILE).exists());
+
+        save_deps(&test_env, &store);
+        assert_eq!(modified(), uploaded);
+
+        assert!(super::main(&store, true).is_ok());
+        test_env.write("deps/b.txt", "changed");
+        let save_result = create_cache::main(&store);
+
+        assert!(save_result.is_ok(), "{}", save_result.unwrap_err());
+        assert_ne!(modified(), uploaded);
+        assert!(store.object(archive_path.as_str()).is_some());
+    }
+
+    #[test]
+    fn download_then_restore_restores_from_cache_folder() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        save_deps(&test_env, &store);
+
+        let download_result = super::main(&store, false);
+        assert!(download_result.is_ok(), "{}", download_result.unwrap_err());
+        assert!(Path::new(".cache/data.json").exists());
+
+        let restore_result = restore_cache::main();
+
+        assert!(restore_result.is_ok(), "{}", restore_result.unwrap_err());
+        assert_eq!(testutils::read(&test_env.path().join("deps/lib/a.txt")), "a");
+    }
+
+    #[test]
+    fn download_without_cache_is_a_miss() {
+        let _test_env = TestEnv::new();
+        let store = MemoryStore::new();
+
+        let download_result = super::main(&store, true);
+
+        assert!(download_result.is_err_and(|error| error.is_not_found()));
+        assert!(testutils::read(Path::new("cache.env")).contains("CACHE_HIT=false"));
+    }
+
+    #[test]
+    fn download_falls_back_to_default_branch() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        save_deps(&test_env, &store);
+        test_env.set("CI_COMMIT_BRANCH", "feature");
+        test_env.set("CI_DEFAULT_BRANCH", "main");
+
+        let download_result = super::main(&store, true);
+
+        assert!(download_result.is_ok(), "{}", download_result.unwrap_err());
+        assert_eq!(testutils::read(Path::new(super::MATCHED_KEY_FILE)), cache_key::get_cache_key_for_branch("main"));
+        assert!(testutils::read(Path::new("cache.env")).contains("CACHE_HIT=false"));
+        assert!(test_env.path().join("deps/b.txt").exists());
+    }
+
+    #[test]
+    fn download_ignores_caches_of_branches_sharing_the_prefix() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        test_env.write("Cargo.lock", "v1");
+        test_env.set("CACHE_KEY_FILES", "Cargo.lock");
+        test_env.set("CI_COMMIT_BRANCH", "main-old");
+        save_deps(&test_env, &store);
+        test_env.set("CI_COMMIT_BRANCH", "main");
+
+        assert!(super::main(&store, true).is_err_and(|error| error.is_not_found()));
+
+        save_deps(&test_env, &store);
+        let outdated_key = cache_key::get_cache_key();
+        test_env.write("Cargo.lock", "v2");
+
+        let download_result = super::main(&store, true);
+
+        assert!(download_result.is_ok(), "{}", download_result.unwrap_err());
+        assert_eq!(testutils::read(Path::new(super::MATCHED_KEY_FILE)), outdated_key);
+    }
+
+    #[test]
+    fn download_prefers_the_newest_matching_cache() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        test_env.set("CACHE_KEY_FILES", "Cargo.lock");
+        let mut keys: Vec<String> = Vec::new();
+
+        for version in ["v1", "v2"] {
+            test_env.write("Cargo.lock", version);
+            save_deps(&test_env, &store);
+            keys.push(cache_key::get_cache_key());
+        }
+
+        test_env.write("Cargo.lock", "v3");
+        let archive_path = |key: &str| storage::get_object_path(format!("{}.zip", key).as_str());
+        let matched_key = || testutils::read(Path::new(super::MATCHED_KEY_FILE));
+
+        // Both caches match the key, the order of their names doesn't matter.
+        for newest in [0, 1, 0] {
+            store.insert(archive_path(&keys[newest]).as_str(), store.object(archive_path(&keys[newest]).as_str()).unwrap());
+
+            let download_result = super::main(&store, true);
+
+            assert!(download_result.is_ok(), "{}", download_result.unwrap_err());
+            assert_eq!(matched_key(), keys[newest]);
+        }
+
+        // A corrupt newest cache doesn't end the key, the older one is restored instead.
+        let mut archive = store.object(archive_path(&keys[1]).as_str()).unwrap();
+        let middle = archive.len() / 2;
+        archive[middle] ^= 0xff;
+        store.insert(archive_path(&keys[1]).as_str(), archive);
+        fs::remove_dir_all(test_env.path().join("deps")).unwrap();
+
+        let download_result = super::main(&store, true);
+
+        assert!(download_result.is_ok(), "{}", download_result.unwrap_err());
+        assert_eq!(matched_key(), keys[0]);
+        assert_eq!(testutils::read(&test_env.path().join("deps/b.txt")), "b");
+    }
+
+    #[test]
+    fn download_fails_on_archives_failing_the_integrity_check() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        test_env.set("CI_DEFAULT_BRANCH", "main");
+        save_deps(&test_env, &store);
+        test_env.set("CI_COMMIT_BRANCH", "feature");
+        save_deps(&test_env, &store);
+
+        // Changing a single byte of the archive on the storage backend fails the download instead of falling back to the next key.
+        let archive_file_name = format!("{}.zip", cache_key::get_cache_key());
+        let archive_path = storage::get_object_path(archive_file_name.as_str());
+        let mut archive = store.object(archive_path.as_str()).unwrap();
+        let middle = archive.len() / 2;
+        archive[middle] ^= 0xff;
+        store.insert(archive_path.as_str(), archive);
+
+        let download_result = super::main(&store, true);
+
+        assert!(download_result.is_err());
+        let download_error = download_result.unwrap_err();
+        assert_eq!(download_error.exit_code(), 8);
+        assert!(download_error.to_string().contains(archive_file_name.as_str()), "{}", download_error);
+        assert!(download_error.to_string().contains("Expected the sha256 checksum"), "{}", download_error);
+        assert!(testutils::read(Path::new("cache.env")).contains("CACHE_HIT=false"));
+        assert!(!test_env.path().join("deps").exists());
+        assert!(!test_env.path().join(archive_file_name).exists());
+    }
+}
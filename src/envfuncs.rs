@@ -1,6 +1,6 @@
-use std::{env, process};
+use std::{env, fs, path::PathBuf, process};
 
-use crate::datas::enums;
+use crate::{cache_key, datas::{enums, errors, structs}, helpers};
 
 /// The function [`get_env_if_startswith`] returns a vector of environment variable values if their names
 /// start with a given string.
@@ -14,8 +14,23 @@ use crate::datas::enums;
 /// The function [`get_env_if_startswith`] returns a vector of strings [`Vec<String>`] containing the
 /// values of environment variables that start with the specified `name`.
 pub fn get_env_if_startswith(name: &str) -> Vec<String>{
+    return get_env_with_suffix(name).into_iter().map(|(_, value)| value).collect();
+}
+
+/// The function [`get_env_with_suffix`] returns the environment variables whose names start with a given string,
+/// together with the rest of their name (e.g. `nodemodules` for `cachepath_nodemodules`).
+/// 
+/// Arguments:
+/// 
+/// * `name`: type of `&str` that represents the prefix that you want to check for in the enviorement variable names.
+/// 
+/// Returns:
+/// 
+/// The function [`get_env_with_suffix`] returns a vector of tuples [`Vec<(String, String)>`] containing the
+/// suffix of the name and the value of environment variables that start with the specified `name`.
+pub fn get_env_with_suffix(name: &str) -> Vec<(String, String)>{
 
-    let mut found_variables:Vec<String> = Vec::new();
+    let mut found_variables:Vec<(String, String)> = Vec::new();
     for (n,v) in env::vars_os() {
         let env_var = n.to_str().unwrap_or("failed");
  
@@ -25,9 +40,9 @@ pub fn get_env_if_startswith(name: &str) -> Vec<String>{
             Please validate it's name is a valid env var name (UTF-8). 
             Skipping this one.");
             continue;
-        } if env_var.starts_with(name) {
+        } if let Some(suffix) = env_var.strip_prefix(name) {
              let vstr = v.to_str().expect(format!("Could not convert value of the enviorement variable {env_var} to string.").as_str());
-             found_variables.push(vstr.to_string());
+             found_variables.push((suffix.to_string(), vstr.to_string()));
         }
     }
  
@@ -55,7 +70,7 @@ fn safe_get_envvar(envvarval: &str, errmsg: &str, exitonfail: bool) -> String{
     if get_envvar_result.is_err(){
         if exitonfail{
             eprintln!("{}", errmsg);
-            process::exit(2);
+            process::exit(errors::EXIT_CONFIG);
         }
         
         println!("{}", errmsg);
@@ -105,6 +120,348 @@ pub fn get_webdavaddr() -> String {
     true);
 }
 
+/// [`get_cache_backend`] returns the storage backend selected via the `CACHE_BACKEND` environment variable.
+/// Defaults to [`enums::BackendType::WebDav`] if the variable is not set.
+/// 
+/// Returns:
+/// 
+/// A [`enums::BackendType`] value.
+pub fn get_cache_backend() -> enums::BackendType {
+    let backend_name = env::var("CACHE_BACKEND").unwrap_or_default();
+    let backend = enums::BackendType::from_name(backend_name.as_str());
+
+    if backend.is_none(){
+        eprintln!("CACHE_BACKEND env var contains the unknown backend {}. 
+        Supported backends are: webdav, ftp, ftps, s3, fs", backend_name);
+        process::exit(errors::EXIT_CONFIG);
+    }
+
+    return backend.unwrap();
+}
+
+/// [`get_compression`] returns the compression selected via the `CACHE_COMPRESSION` environment variable.
+/// The value has the format `<algorithm>` or `<algorithm>:<level>` (e.g. `zstd:3`). Defaults to `deflate` if the variable is not set.
+/// 
+/// Returns:
+/// 
+/// A [`structs::Compression`] value.
+pub fn get_compression() -> structs::Compression {
+    let compression_value = env::var("CACHE_COMPRESSION").unwrap_or_default();
+
+    if compression_value.trim().is_empty(){
+        return structs::Compression { compression_type: enums::CompressionType::Deflate, level: None };
+    }
+
+    let compression = structs::Compression::parse(compression_value.as_str());
+
+    if compression.is_err(){
+        eprintln!("CACHE_COMPRESSION env var contains the invalid value {}. 
+        Error was: {}", compression_value, compression.unwrap_err());
+        process::exit(errors::EXIT_CONFIG);
+    }
+
+    return compression.unwrap();
+}
+
+/// [`get_archive_format`] returns the archive format selected via the `CACHE_ARCHIVE_FORMAT` environment variable.
+/// Defaults to [`enums::ArchiveFormat::Zip`] if the variable is not set.
+/// 
+/// Returns:
+/// 
+/// A [`enums::ArchiveFormat`] value.
+pub fn get_archive_format() -> enums::ArchiveFormat {
+    let format_name = env::var("CACHE_ARCHIVE_FORMAT").unwrap_or_default();
+    let format = enums::ArchiveFormat::from_name(format_name.as_str());
+
+    if format.is_none(){
+        eprintln!("CACHE_ARCHIVE_FORMAT env var contains the unknown format {}. 
+        Supported formats are: zip, tar.zst, tar.gz", format_name);
+        process::exit(errors::EXIT_CONFIG);
+    }
+
+    return format.unwrap();
+}
+
+/// [`get_ftp_addr`] returns the value of the `FTPADDR` environment variable, or
+/// displays an error message if it is not set.
+/// 
+/// Returns:
+/// 
+/// A string value in the format of `host` or `host:port`.
+pub fn get_ftp_addr() -> String {
+    return safe_get_envvar("FTPADDR", 
+    "FTPADDR env var not set. 
+    Please make sure to set one otherwise we don't know where to connect.", 
+    true);
+}
+
+/// [`get_ftp_user`] returns the value of the `FTPUSER` environment variable, or
+/// displays an error message if it is not set.
+/// 
+/// Returns:
+/// 
+/// A string value is being returned.
+pub fn get_ftp_user() -> String {
+    return safe_get_envvar("FTPUSER", 
+    "FTPUSER env var not set. 
+    Please make sure to set one. We currently do not support anonymous ftp servers.", 
+    true);
+}
+
+/// [`get_ftp_password`] returns the value of the `FTPPASS` environment variable, or
+/// displays an error message if it is not set.
+/// 
+/// Returns:
+/// 
+/// A string value is being returned.
+pub fn get_ftp_password() -> String {
+    return safe_get_envvar("FTPPASS", 
+    "FTPPASS env var not set. 
+    Please make sure to set one. We currently do not support anonymous ftp servers.", 
+    true);
+}
+
+/// [`get_ftps_enabled`] checks if the connection to the ftp server should be secured via explicit TLS.
+/// This is the case if `CACHE_BACKEND` is set to `ftps` or if the `FTPS` environment variable is set to `true`.
+/// 
+/// Returns:
+/// 
+/// A [`bool`] indicating if FTPS should be used.
+pub fn get_ftps_enabled() -> bool {
+    let backend_name = env::var("CACHE_BACKEND").unwrap_or_default();
+    let ftps = env::var("FTPS").unwrap_or_default();
+
+    return backend_name.trim().eq_ignore_ascii_case("ftps") || ftps.trim().eq_ignore_ascii_case("true");
+}
+
+/// [`get_s3_endpoint`] returns the value of the `S3ENDPOINT` environment variable, or
+/// displays an error message if it is not set.
+/// 
+/// Returns:
+/// 
+/// A string value containing the base url of the S3 compatible server (e.g. `https://minio.example.com:9000`).
+pub fn get_s3_endpoint() -> String {
+    return safe_get_envvar("S3ENDPOINT", 
+    "S3ENDPOINT env var not set. 
+    Please make sure to set one otherwise we don't know where to connect.", 
+    true);
+}
+
+/// [`get_s3_bucket`] returns the value of the `S3BUCKET` environment variable, or
+/// displays an error message if it is not set.
+/// 
+/// Returns:
+/// 
+/// A string value containing the name of the bucket the cache is stored in.
+pub fn get_s3_bucket() -> String {
+    return safe_get_envvar("S3BUCKET", 
+    "S3BUCKET env var not set. 
+    Please make sure to set one otherwise we don't know where to store the cache.", 
+    true);
+}
+
+/// [`get_s3_prefix`] returns the value of the optional `S3PREFIX` environment variable.
+/// 
+/// Returns:
+/// 
+/// A string value containing the prefix all objects are stored under, or an empty string if it is not set.
+pub fn get_s3_prefix() -> String {
+    return env::var("S3PREFIX").unwrap_or_default().trim_matches('/').to_string();
+}
+
+/// [`get_s3_region`] returns the value of the optional `S3REGION` environment variable.
+/// 
+/// Returns:
+/// 
+/// A string value containing the region used for signing requests. Defaults to `us-east-1`.
+pub fn get_s3_region() -> String {
+    let region = env::var("S3REGION").unwrap_or_default();
+
+    if region.trim().is_empty(){
+        return String::from("us-east-1");
+    }
+
+    return region;
+}
+
+/// [`get_s3_access_key`] returns the value of the `S3ACCESSKEY` environment variable, or
+/// displays an error message if it is not set.
+/// 
+/// Returns:
+/// 
+/// A string value is being returned.
+pub fn get_s3_access_key() -> String {
+    return safe_get_envvar("S3ACCESSKEY", 
+    "S3ACCESSKEY env var not set. 
+    Please make sure to set one. We currently do not support anonymous buckets.", 
+    true);
+}
+
+/// [`get_s3_secret_key`] returns the value of the `S3SECRETKEY` environment variable, or
+/// displays an error message if it is not set.
+/// 
+/// Returns:
+/// 
+/// A string value is being returned.
+pub fn get_s3_secret_key() -> String {
+    return safe_get_envvar("S3SECRETKEY", 
+    "S3SECRETKEY env var not set. 
+    Please make sure to set one. We currently do not support anonymous buckets.", 
+    true);
+}
+
+/// [`get_fs_root`] returns the value of the `FSROOT` environment variable, or
+/// displays an error message if it is not set.
+/// 
+/// Returns:
+/// 
+/// A string value containing the directory the cache is stored in (e.g. the mount point of a NFS share).
+pub fn get_fs_root() -> String {
+    return safe_get_envvar("FSROOT", 
+    "FSROOT env var not set. 
+    Please make sure to set one otherwise we don't know where to store the cache.", 
+    true);
+}
+
+/// [`get_project_dir`] returns the directory the project is checked out to. Cached objects inside of it are stored relative to it,
+/// so they are restored correctly on runners that check the project out to a different directory.
+/// 
+/// Returns:
+/// 
+/// The value of the `CI_PROJECT_DIR` environment variable or the current directory if it is not set.
+pub fn get_project_dir() -> PathBuf {
+    let project_dir = env::var("CI_PROJECT_DIR").ok().filter(|project_dir| !project_dir.is_empty()).map(PathBuf::from).unwrap_or_default();
+    return env::current_dir().map(|current_dir| current_dir.join(&project_dir)).unwrap_or(project_dir);
+}
+
+/// [`get_home_dir`] returns the home directory of the user running the job. Cached objects inside of it (and outside of
+/// the project directory) are stored relative to it.
+/// 
+/// Returns:
+/// 
+/// The value of the `HOME` or `USERPROFILE` environment variable or [`None`] if neither is set.
+pub fn get_home_dir() -> Option<PathBuf> {
+    return ["HOME", "USERPROFILE"].iter()
+        .filter_map(|home_var| env::var(home_var).ok())
+        .find(|home_dir| !home_dir.is_empty())
+        .map(PathBuf::from);
+}
+
+/// [`get_restore_roots`] returns the directories the cache may be restored into.
+/// These are the project directory (`CI_PROJECT_DIR` and the current directory), the home directory of the user
+/// and any directories listed in the `CACHE_RESTORE_ROOTS` environment variable, seperated like the `PATH` variable.
+/// 
+/// Returns:
+/// 
+/// A [`Vec<PathBuf>`] of canonicalized directories. Directories that don't exist are left out.
+pub fn get_restore_roots() -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+
+    for root_var in ["CI_PROJECT_DIR", "HOME", "USERPROFILE"]{
+        if let Ok(root) = env::var(root_var) {
+            roots.push(PathBuf::from(root));
+        }
+    }
+
+    if let Ok(current_dir) = env::current_dir() {
+        roots.push(current_dir);
+    }
+
+    if let Some(extra_roots) = env::var_os("CACHE_RESTORE_ROOTS") {
+        roots.extend(env::split_paths(&extra_roots));
+    }
+
+    return roots.iter()
+        .filter(|root| !root.as_os_str().is_empty())
+        .filter_map(|root| fs::canonicalize(root).ok())
+        .collect();
+}
+
+/// [`get_dotenv_file`] returns the file the status of a download is written to.
+/// 
+/// Returns:
+/// 
+/// The value of the `CACHE_DOTENV` environment variable or `cache.env` if it is not set.
+pub fn get_dotenv_file() -> String {
+    let dotenv_file = env::var("CACHE_DOTENV").unwrap_or_default();
+
+    if dotenv_file.trim().is_empty(){
+        return String::from("cache.env");
+    }
+
+    return dotenv_file;
+}
+
+/// [`get_fail_on_miss`] checks if a download that finds no cache should fail.
+/// This is the case if the `CACHE_FAIL_ON_MISS` environment variable is set to `true`.
+/// 
+/// Returns:
+/// 
+/// A [`bool`] indicating if a miss should exit with a non 0 exit code.
+pub fn get_fail_on_miss() -> bool {
+    return env::var("CACHE_FAIL_ON_MISS").unwrap_or_default().trim().eq_ignore_ascii_case("true");
+}
+
+/// [`get_cross_os`] checks if caches created on another operating system may be restored.
+/// This is the case if the `CACHE_CROSS_OS` environment variable is set to `true`.
+///
+/// Returns:
+///
+/// A [`bool`] indicating if objects of other operating systems are restored.
+pub fn get_cross_os() -> bool {
+    return env::var("CACHE_CROSS_OS").unwrap_or_default().trim().eq_ignore_ascii_case("true");
+}
+
+/// [`get_path_remap`] returns the pairs of paths used to map absolute restore locations between operating systems.
+/// The `CACHE_PATH_REMAP` environment variable contains the pairs seperated by `,`, each written as `<path>=<path>`
+/// (e.g. `C:\Users\ci=/home/ci`). The variable is parsed once by the restore commands, which pass the pairs on
+/// (see [`structs::RestoreData::get_restore_to`]).
+///
+/// Returns:
+///
+/// A [`Vec<(String, String)>`] of the pairs as portable paths (see [`helpers::to_portable_path`]) or a config error if a pair is invalid.
+pub fn get_path_remap() -> Result<Vec<(String, String)>, errors::CacheError> {
+    let mut remap: Vec<(String, String)> = Vec::new();
+
+    for pair in env::var("CACHE_PATH_REMAP").unwrap_or_default().split(',').filter(|pair| !pair.trim().is_empty()) {
+        let paths = pair.split_once('=').map(|(from, to)| (to_portable_remap_path(from), to_portable_remap_path(to)));
+
+        // A drive without a folder (e.g. C:\) is a valid absolute path as well, the trailing seperator is removed by the conversion.
+        let is_absolute = |path: &str| helpers::is_portable_absolute(path) || (path.len() == 2 && path.ends_with(':'));
+
+        if !paths.as_ref().is_some_and(|(from, to)| is_absolute(from) && is_absolute(to)) {
+            return Err(errors::CacheError::Config(format!("CACHE_PATH_REMAP env var contains the invalid value {}. Every pair has to be two absolute paths written as <path>=<path>.", pair)));
+        }
+
+        remap.push(paths.unwrap());
+    }
+
+    return Ok(remap);
+}
+
+/// Converts a path of `CACHE_PATH_REMAP` into a portable path. The path may belong to any operating system, so backslashes
+/// are always treated as seperators.
+fn to_portable_remap_path(path: &str) -> String {
+    let portable_path = path.trim().replace('\\', "/");
+    let portable_path = portable_path.trim_end_matches('/');
+    let mut chars = portable_path.chars();
+
+    return match (chars.next(), chars.next()) {
+        (Some(drive), Some(':')) => format!("{}{}", drive.to_ascii_uppercase(), &portable_path[1..]),
+        _ => portable_path.to_string()
+    };
+}
+
+/// [`get_force_upload`] checks if the cache should be uploaded even though it's contents did not change.
+/// This is the case if the `CACHE_FORCE_UPLOAD` environment variable is set to `true`.
+///
+/// Returns:
+///
+/// `true` if the upload should never be skipped.
+pub fn get_force_upload() -> bool {
+    return env::var("CACHE_FORCE_UPLOAD").unwrap_or_default().trim().eq_ignore_ascii_case("true");
+}
+
 /// The function [`get_projectid`] returns the value of the `CI_PROJECT_ID` environment variable, or
 /// prompts the user to set it manually if it is not found.
 /// 
@@ -131,23 +488,32 @@ pub fn get_branch_name() -> String{
     true);
 }
 
-/// The function [`get_zip_file_name`] returns a string representing the name of a zip file based on the
-/// operating system type and branch name.
+/// The function [`get_archive_file_name`] returns a string representing the name of the archive based on the
+/// cache key of the current job (see [`cache_key::get_cache_key`]) and the configured archive format.
 /// 
 /// Returns:
 /// 
-/// A string containing the zip file name.
-pub fn get_zip_file_name() -> String {
-    let ostype: enums::OsType;
-    if cfg!(windows) {
-        ostype = enums::OsType::Windows;
-    }else if cfg!(unix){
-        ostype = enums::OsType::Unix;
+/// A string containing the archive file name.
+pub fn get_archive_file_name() -> String {
+    return format!("{}{}", cache_key::get_cache_key(), get_archive_format().extension());
+}
 
-    }else{
-        eprintln!("OsType is currently not supported. Exiting with error");
-        process::exit(1);
+/// [`get_work_dir`] returns the directory the files of the tool (the archive that is uploaded or downloaded and the digest of the
+/// last download) are kept in. It is a directory inside of the temporary directory named after the project directory,
+/// so those files never end up in the project or in one of the cached folders. The directory is created if it does not exist.
+/// 
+/// Returns:
+/// 
+/// The [`PathBuf`] of the directory or an error if it could not be created.
+pub fn get_work_dir() -> Result<PathBuf, errors::CacheError> {
+    let mut project_hash = sha256::digest(get_project_dir().to_string_lossy().as_bytes());
+    project_hash.truncate(16);
+    let work_dir = env::temp_dir().join(format!("worker-cache-{}", project_hash));
+    let create_result = fs::create_dir_all(&work_dir);
+
+    if create_result.is_err(){
+        return Err(errors::CacheError::io("Could not create the work directory in the temporary directory. Please make sure we have write permissions in it.", create_result.unwrap_err()));
     }
 
-    return format!("{}-{}.zip", ostype.to_string(), get_branch_name());
-}
\ No newline at end of file
+    return Ok(work_dir);
+}
@@ -0,0 +1,166 @@
+use std::{env, path::{Path, PathBuf}};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::envfuncs;
+
+/// Options used for all patterns. `*` does not match `/`, so `**` has to be used to match multiple directories.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false
+};
+
+/// Checks if a cached path contains glob characters and has to be expanded.
+pub fn is_glob(path: &str) -> bool {
+    return path.contains(['*', '?', '[']);
+}
+
+/// [`expand_paths`] expands the glob patterns in a list of cached paths. Paths without glob characters are kept as they are.
+/// Relative patterns are expanded from the current directory. Matches that are located inside of another match
+/// (e.g. nested `node_modules` folders matched by `**/node_modules`) are left out, since they are cached with their parent.
+/// Every path keeps the suffix of the enviorement variable it was configured with, matches of a pattern share it.
+///
+/// Arguments:
+///
+/// * `paths`: type of [`Vec<(String, String)>`], the suffixes and values of the `cachepath_` or `cachefile_` enviorement variables.
+/// * `directories`: type of [`bool`], if only directories (`true`) or only files (`false`) are matched by patterns.
+///
+/// Returns:
+/// A [`Vec<(String, String)>`] of the literal paths and all matches of the patterns, together with their suffix.
+pub fn expand_paths(paths: Vec<(String, String)>, directories: bool) -> Vec<(String, String)> {
+    let mut expanded_paths: Vec<(String, String)> = Vec::new();
+
+    for (entry_name, path) in paths {
+        if !is_glob(path.as_str()) {
+            expanded_paths.push((entry_name, path));
+            continue;
+        }
+
+        let glob_result = glob::glob_with(path.as_str(), MATCH_OPTIONS);
+
+        if glob_result.is_err() {
+            println!("The cached path {} is not a valid glob pattern. Skipping it.", path);
+            continue;
+        }
+
+        let mut matches: Vec<PathBuf> = glob_result.unwrap()
+            .filter_map(|matched_path| matched_path.ok())
+            .filter(|matched_path| matched_path.is_dir() == directories)
+            .collect();
+        matches.sort();
+
+        let mut kept_matches: Vec<PathBuf> = Vec::new();
+
+        for matched_path in matches {
+            if !kept_matches.iter().any(|kept_match| matched_path.starts_with(kept_match)) {
+                kept_matches.push(matched_path);
+            }
+        }
+
+        if kept_matches.is_empty() {
+            println!("The pattern {} did not match anything.", path);
+        }
+
+        expanded_paths.extend(kept_matches.iter().map(|matched_path| (entry_name.clone(), matched_path.to_string_lossy().to_string())));
+    }
+
+    return expanded_paths;
+}
+
+/// [`matches_any`] checks if a restored path was configured to be cached, either literally or through a glob pattern.
+///
+/// Arguments:
+///
+/// * `paths`: type of [`&[String]`], the values of the `cachepath_` or `cachefile_` enviorement variables.
+/// * `path`: type of [`&str`], the path of the cached object (the restore location followed by it's name).
+///
+/// Returns:
+/// If any of the configured paths matches.
+pub fn matches_any(paths: &[String], path: &str) -> bool {
+    return paths.iter().any(|configured_path| {
+        if !is_glob(configured_path.as_str()) {
+            return configured_path == path;
+        }
+
+        return Pattern::new(absolute_pattern(configured_path).as_str())
+            .is_ok_and(|pattern| pattern.matches_path_with(Path::new(path), MATCH_OPTIONS));
+    });
+}
+
+/// Makes a relative pattern absolute by prefixing it with the current directory.
+fn absolute_pattern(pattern: &str) -> String {
+    if Path::new(pattern).is_absolute() {
+        return pattern.to_string();
+    }
+
+    let current_dir = env::current_dir().unwrap_or_default();
+    return format!("{}/{}", Pattern::escape(current_dir.to_string_lossy().as_ref()), pattern.trim_start_matches("./"));
+}
+
+/// [`ExcludeRules`] contains the patterns of the `cacheexclude_` enviorement variables. Excluded paths are not put into the archive.
+#[derive(Debug, Default)]
+pub struct ExcludeRules {
+    patterns: Vec<Pattern>
+}
+
+impl ExcludeRules {
+    /// Creates the [`ExcludeRules`] from all enviorement variables starting with `cacheexclude_`.
+    /// Invalid patterns are reported and ignored.
+    pub fn from_env() -> ExcludeRules {
+        let mut patterns: Vec<Pattern> = Vec::new();
+
+        for exclude in envfuncs::get_env_if_startswith("cacheexclude_") {
+            let pattern = Pattern::new(exclude.trim_start_matches("./").trim_end_matches('/'));
+
+            if pattern.is_err() {
+                println!("The exclude pattern {} is not a valid glob pattern. Ignoring it.", exclude);
+                continue;
+            }
+
+            patterns.push(pattern.unwrap());
+        }
+
+        return ExcludeRules { patterns };
+    }
+
+    /// [`ExcludeRules::is_excluded`] checks if a path or one of it's parent directories matches an exclude pattern.
+    /// Absolute paths are matched as they are and relative to the current directory, relative paths
+    /// (e.g. the names of the entries inside of the archive) are matched as they are.
+    ///
+    /// Arguments:
+    ///
+    /// * `path`: type of [`&Path`], the path to check.
+    ///
+    /// Returns:
+    /// If the path has to be left out of the cache.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+
+        let current_dir = env::current_dir().unwrap_or_default();
+        let relative_path = path.strip_prefix(&current_dir).ok();
+
+        return path.ancestors()
+            .chain(relative_path.into_iter().flat_map(|relative_path| relative_path.ancestors()))
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| self.patterns.iter().any(|pattern| pattern.matches_path_with(ancestor, MATCH_OPTIONS)));
+    }
+
+    /// [`ExcludeRules::skips_entry`] checks if an entry inside of a cached folder has to be left out. Entries are matched by their
+    /// source path and by the path they get inside of the archive.
+    ///
+    /// Arguments:
+    ///
+    /// * `path`: type of [`&Path`], the source path of the entry.
+    /// * `object_path`: type of [`&Path`], the source path of the cached folder containing the entry.
+    /// * `cache_name`: type of [`&str`], the name of the cached folder inside of the archive.
+    ///
+    /// Returns:
+    /// If the entry has to be left out of the cache.
+    pub fn skips_entry(&self, path: &Path, object_path: &Path, cache_name: &str) -> bool {
+        return self.is_excluded(path)
+            || path.strip_prefix(object_path).is_ok_and(|relative_path| self.is_excluded(&Path::new(cache_name).join(relative_path)));
+    }
+}
@@ -1,11 +1,62 @@
-use std::{path::Path, io, fs};
+use std::{path::{Component, Path, PathBuf}, io, fs};
+use filetime::FileTime;
+use walkdir::WalkDir;
 
-use rustydav::client;
+use crate::datas::{enums, errors::CacheError, structs::RestoreData};
+use crate::globs::ExcludeRules;
+use crate::{digest, envfuncs};
+use crate::storage::{self, CacheStore};
 
-use crate::{envfuncs, datas::enums};
+/// [`walk_restore_data`] visits every file, folder and symlink of the cached objects in the order they are stored in the archive.
+/// Cached folders are walked recursively without following the symlinks inside of them and excluded entries
+/// (see [`ExcludeRules::skips_entry`]) are skipped together with their contents.
+/// 
+/// Arguments:
+/// 
+/// * `restore_data`: type of [`&[RestoreData]`], the objects that are cached.
+/// * `excludes`: type of [`&ExcludeRules`], the exclude rules applied to the cached folders.
+/// * `visit`: type of [`&mut dyn FnMut(&Path, &Path, &fs::Metadata) -> io::Result<()>`], called with the source path of every entry,
+/// it's name inside of the archive (`<cache_name>/<relative path>`) and it's metadata.
+/// 
+/// Returns:
+/// An error if an entry could not be read or `visit` failed.
+pub fn walk_restore_data(restore_data: &[RestoreData], excludes: &ExcludeRules, visit: &mut dyn FnMut(&Path, &Path, &fs::Metadata) -> io::Result<()>) -> io::Result<()> {
+    for restore_obj in restore_data {
+        // The objects were found by this job, so their locations are absolute on this system and never remapped.
+        let object_path = restore_obj.get_restore_to(&[]).join(&restore_obj.restore_obj_name);
+        let is_directory = restore_obj.cachetype == enums::CacheType::Directory;
+        let mut it = WalkDir::new(&object_path).follow_links(false).sort_by_file_name().into_iter();
+
+        while let Some(entry) = it.next() {
+            let entry = entry?;
+
+            if is_directory && excludes.skips_entry(entry.path(), &object_path, restore_obj.get_cache_name()) {
+                if entry.file_type().is_dir() {
+                    it.skip_current_dir();
+                }
+
+                continue;
+            }
+
+            // A cached folder may be a symlink itself, it is stored like a normal folder containing the contents of it's target.
+            let metadata = if is_directory && entry.depth() == 0 { fs::metadata(entry.path())? } else { fs::symlink_metadata(entry.path())? };
+            let relative_path = entry.path().strip_prefix(&object_path).unwrap_or(entry.path());
+            let mut name = PathBuf::from(restore_obj.get_cache_name());
+
+            if !relative_path.as_os_str().is_empty() {
+                name.push(relative_path);
+            }
+
+            visit(entry.path(), &name, &metadata)?;
+        }
+    }
+
+    return Ok(());
+}
 
 /// The [`copy_recursively`] function copies all files and directories from a source directory to a
-/// destination directory
+/// destination directory. Symlinks are copied as symlinks and the permissions and modification times
+/// of all entries are preserved (see [`copy_file`]).
 /// 
 /// Arguments:
 /// 
@@ -15,70 +66,570 @@ use crate::{envfuncs, datas::enums};
 /// copied to. It should implement the [`AsRef<Path>`] trait, which means it can be any type that can be
 /// converted to a [`Path`] reference.
 /// 
+/// * `skip`: type of [`&dyn Fn(&Path) -> bool`], called with the source path of every entry. Entries it returns `true` for
+/// are not copied (see [`crate::globs::ExcludeRules`]).
+/// 
 /// Returns:
 /// Returns a result, indicating if we ran successfully
-pub fn copy_recursively(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+pub fn copy_recursively(from: impl AsRef<Path>, to: impl AsRef<Path>, skip: &dyn Fn(&Path) -> bool) -> io::Result<()> {
     fs::create_dir_all(&to)?;
     
-    for entry in fs::read_dir(from)? {
+    for entry in fs::read_dir(&from)? {
         let entry = entry?;
         let file_type = entry.file_type()?;
+        if skip(&entry.path()) {
+            continue;
+        }
         if file_type.is_dir() {
-            copy_recursively(entry.path(), to.as_ref().join(entry.file_name()))?;
+            copy_recursively(entry.path(), to.as_ref().join(entry.file_name()), skip)?;
         } else {
-            fs::copy(entry.path(), to.as_ref().join(entry.file_name()))?;
+            copy_file(entry.path(), to.as_ref().join(entry.file_name()))?;
         }
     }
+
+    // The directory metadata is copied last since creating the entries inside of it changes it's modification time.
+    let metadata = fs::metadata(&from)?;
+    fs::set_permissions(&to, metadata.permissions())?;
+    filetime::set_file_mtime(&to, FileTime::from_last_modification_time(&metadata))?;
     Ok(())
 }
 
+/// The [`copy_file`] function copies a single file while keeping it's permissions and modification time.
+/// If `from` is a symlink, the link itself is copied instead of the file it points to.
+/// An existing symlink at `to` is replaced instead of writing through it.
+/// 
+/// Arguments:
+/// 
+/// * `from`: type of [`Path`], the file or symlink to copy.
+/// * `to`: type of [`Path`], the path the copy is created at.
+/// 
+/// Returns:
+/// Returns a result, indicating if we ran successfully
+pub fn copy_file(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(&from)?;
+
+    if fs::symlink_metadata(&to).is_ok_and(|existing| existing.file_type().is_symlink() || metadata.file_type().is_symlink()) {
+        fs::remove_file(&to)?;
+    }
+
+    if metadata.file_type().is_symlink() {
+        return create_symlink(&fs::read_link(&from)?, to.as_ref());
+    }
+
+    // fs::copy copies the permissions of the file as well.
+    fs::copy(&from, &to)?;
+    filetime::set_file_mtime(&to, FileTime::from_last_modification_time(&metadata))?;
+    Ok(())
+}
+
+/// The [`create_symlink`] function creates a symlink at `link` pointing to `target`.
+/// On windows the link is created as a directory link if `target` resolves to a directory.
+/// 
+/// Arguments:
+/// 
+/// * `target`: type of [`&Path`], the path the link points to. Relative targets are resolved from the directory of the link.
+/// * `link`: type of [`&Path`], the path the link is created at.
+/// 
+/// Returns:
+/// Returns a result, indicating if we ran successfully
+pub fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        return std::os::unix::fs::symlink(target, link);
+    }
+
+    #[cfg(windows)]
+    {
+        let resolved_target = link.parent().map(|parent| parent.join(target)).unwrap_or(target.to_path_buf());
+
+        if resolved_target.is_dir() {
+            return std::os::windows::fs::symlink_dir(target, link);
+        }
+
+        return std::os::windows::fs::symlink_file(target, link);
+    }
+}
+
+
+/// The [`normalize_path`] function resolves `.` and `..` components of a path without accessing the file system.
+/// 
+/// Arguments:
+/// 
+/// * `path`: type of [`&Path`], the path to normalize.
+/// 
+/// Returns:
+/// The normalized path. `..` components can't leave the root of an absolute path.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => { normalized.pop(); }
+            component => { normalized.push(component); }
+        }
+    }
+
+    return normalized;
+}
+
+/// [`to_portable_path`] converts a path into the form stored in the data.json file, which is the same on all operating systems.
+/// Portable paths use `/` as seperator and upper case drive letters (e.g. `C:/Users/ci` for `c:\Users\ci`).
+///
+/// Arguments:
+///
+/// * `path`: type of [`&Path`], the path to convert.
+///
+/// Returns:
+/// The portable path as [`String`].
+pub fn to_portable_path(path: &Path) -> String {
+    #[cfg(windows)]
+    {
+        let portable_path = path.to_string_lossy().replace('\\', "/");
+        let mut chars = portable_path.chars();
+
+        return match (chars.next(), chars.next()) {
+            (Some(drive), Some(':')) => format!("{}{}", drive.to_ascii_uppercase(), &portable_path[1..]),
+            _ => portable_path
+        };
+    }
+
+    #[cfg(unix)]
+    {
+        return path.to_string_lossy().to_string();
+    }
+}
+
+/// [`from_portable_path`] converts a path stored in the data.json file (see [`to_portable_path`]) into a path of this operating system.
+/// Absolute paths of other operating systems stay relative (e.g. `C:/Users/ci` on unix) and have to be remapped first (see [`remap_portable_path`]).
+pub fn from_portable_path(path: &str) -> PathBuf {
+    #[cfg(windows)]
+    {
+        return PathBuf::from(path.replace('/', "\\"));
+    }
+
+    #[cfg(unix)]
+    {
+        return PathBuf::from(path);
+    }
+}
+
+/// Checks if a portable path is absolute on any operating system, either starting with `/` or a drive letter (e.g. `C:/`).
+pub fn is_portable_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    return path.starts_with('/') || (bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'/');
+}
+
+/// [`remap_portable_path`] maps an absolute path of another operating system to a path of this one, using the pairs of
+/// `CACHE_PATH_REMAP` (see [`envfuncs::get_path_remap`]). Pairs are applied in both directions, the first pair matching the start
+/// of `path` is used. Drive letters are compared ignoring their casing.
+///
+/// Arguments:
+///
+/// * `path`: type of [`&str`], the portable path stored in the data.json file.
+/// * `remap`: type of [`&[(String, String)]`], the portable path pairs.
+///
+/// Returns:
+/// The remapped portable path or `path` itself if it is already absolute on this operating system or no pair matches.
+pub fn remap_portable_path(path: &str, remap: &[(String, String)]) -> String {
+    if from_portable_path(path).is_absolute() {
+        return path.to_string();
+    }
+
+    for (from, to) in remap {
+        if let Some(rest) = strip_portable_prefix(path, from) {
+            return format!("{}{}", to, rest);
+        }
+
+        if let Some(rest) = strip_portable_prefix(path, to) {
+            return format!("{}{}", from, rest);
+        }
+    }
+
+    return path.to_string();
+}
+
+/// Removes `prefix` from the start of the portable `path` if it matches whole components.
+///
+/// Returns:
+/// The rest of the path including it's leading `/` or [`None`] if `prefix` does not match.
+fn strip_portable_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    if path.len() < prefix.len() || !path.is_char_boundary(prefix.len()) {
+        return None;
+    }
+
+    let (start, rest) = path.split_at(prefix.len());
+    let has_drive = prefix.as_bytes().get(1) == Some(&b':');
+    let matches = if has_drive { start.eq_ignore_ascii_case(prefix) } else { start == prefix };
+
+    if !matches || !(rest.is_empty() || rest.starts_with('/')) {
+        return None;
+    }
+
+    return Some(rest);
+}
+
+/// [`ExtractTarget`] describes where the entries of an archive are extracted to. It is used to extract into the .cache folder
+/// as well as to restore the entries straight to the locations indicated by the data.json file.
+pub struct ExtractTarget<'a> {
+    /// Maps the name of an entry inside of the archive to the path it is extracted to. Entries it returns [`None`] for are skipped.
+    pub destination: &'a dyn Fn(&Path) -> Option<PathBuf>,
+    /// The canonicalized directories entries may be written into.
+    pub roots: &'a [PathBuf],
+    /// If symlinks have to point inside of the `roots`. Symlinks pointing outside of them are skipped.
+    pub check_links: bool
+}
+
+impl ExtractTarget<'_> {
+    /// [`ExtractTarget::resolve`] returns the path an entry of the archive is extracted to.
+    ///
+    /// Returns:
+    /// The path or [`None`] if the entry is skipped or it's name is not a plain relative path.
+    pub fn resolve(&self, name: &Path) -> Option<PathBuf> {
+        if !name.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+            return None;
+        }
+
+        return (self.destination)(name);
+    }
+
+    /// [`ExtractTarget::prepare`] creates the parent directories of `outpath` and removes an existing symlink at `outpath`,
+    /// so files are never written to the file a symlink points to. Existing symlinks to directories are kept for directory entries.
+    ///
+    /// Arguments:
+    ///
+    /// * `outpath`: type of [`&Path`], the path returned by [`ExtractTarget::resolve`].
+    /// * `is_dir`: type of [`bool`], if the entry is a directory.
+    /// * `created_paths`: type of [`&mut Vec<PathBuf>`], the paths created by the extraction so far (see [`track_created_path`]).
+    ///
+    /// Returns:
+    /// `false` if `outpath` resolves outside of the `roots`, e.g. through a symlink extracted earlier, or an error if the
+    /// directories could not be created.
+    pub fn prepare(&self, outpath: &Path, is_dir: bool, created_paths: &mut Vec<PathBuf>) -> Result<bool, CacheError> {
+        let parent_path = outpath.parent();
+
+        if parent_path.is_none() {
+            return Ok(false);
+        }
+
+        // Never write through a symlink that points outside of the roots, the closest existing path decides where the entry ends up.
+        let checked_path = if is_dir { outpath } else { parent_path.unwrap() };
+        let existing_path = checked_path.ancestors().find(|ancestor| ancestor.exists());
+
+        if !existing_path.and_then(|existing_path| fs::canonicalize(existing_path).ok()).is_some_and(|resolved| self.is_below_roots(&resolved)) {
+            return Ok(false);
+        }
+
+        track_created_path(outpath, created_paths);
+        let create_result = fs::create_dir_all(parent_path.unwrap());
+
+        if create_result.is_err() {
+            return Err(CacheError::io("Could not create a directory while extracting the archive.", create_result.unwrap_err()));
+        }
+
+        if !is_dir && fs::symlink_metadata(outpath).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            let remove_result = fs::remove_file(outpath);
+
+            if remove_result.is_err() {
+                return Err(CacheError::io("Could not replace an existing symlink while extracting the archive.", remove_result.unwrap_err()));
+            }
+        }
+
+        return Ok(true);
+    }
+
+    /// [`ExtractTarget::allows_link`] checks if a symlink extracted to `outpath` may point to `link_target`.
+    /// Relative targets are resolved from the canonicalized directory of the link, since the `roots` are canonicalized as well.
+    /// The directory has to exist already (see [`ExtractTarget::prepare`]), otherwise the link is refused.
+    pub fn allows_link(&self, outpath: &Path, link_target: &Path) -> bool {
+        if !self.check_links {
+            return true;
+        }
+
+        let link_dir = outpath.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let link_dir = fs::canonicalize(link_dir);
+
+        if link_dir.is_err() {
+            return false;
+        }
+
+        return self.is_below_roots(&normalize_path(&link_dir.unwrap().join(link_target)));
+    }
+
+    /// Checks if `path` is located inside of one of the `roots`.
+    fn is_below_roots(&self, path: &Path) -> bool {
+        return self.roots.iter().any(|root| path.starts_with(root));
+    }
+}
+
+/// [`track_created_path`] remembers the outermost file or folder that has to be created for `outpath` if it does not exist yet,
+/// so it can be removed by [`remove_extracted`] if the extraction fails. Paths that already existed are never added.
+///
+/// Arguments:
+///
+/// * `outpath`: type of [`&Path`], the path the next entry of the archive is extracted to.
+/// * `created_paths`: type of [`&mut Vec<PathBuf>`], the paths created by the extraction so far.
+pub fn track_created_path(outpath: &Path, created_paths: &mut Vec<PathBuf>) {
+    let created_path = outpath.ancestors()
+        .take_while(|ancestor| !ancestor.as_os_str().is_empty() && fs::symlink_metadata(ancestor).is_err())
+        .last();
+
+    if let Some(created_path) = created_path {
+        if !created_paths.iter().any(|tracked_path| created_path.starts_with(tracked_path)) {
+            created_paths.push(created_path.to_path_buf());
+        }
+    }
+}
+
+/// [`remove_extracted`] removes the files and folders created by an extraction that failed, so no partial cache is left behind.
+/// Failing to remove them is only reported, since the original error is more important.
+///
+/// Arguments:
+///
+/// * `created_paths`: type of [`&[PathBuf]`], the paths collected by [`track_created_path`].
+pub fn remove_extracted(created_paths: &[PathBuf]) {
+    for created_path in created_paths {
+        let is_dir = fs::symlink_metadata(created_path).is_ok_and(|metadata| metadata.is_dir());
+        let remove_result = if is_dir { fs::remove_dir_all(created_path) } else { fs::remove_file(created_path) };
+
+        if remove_result.as_ref().is_err_and(|error| error.kind() != io::ErrorKind::NotFound) {
+            eprintln!("Could not remove {} after the extraction failed.", created_path.display());
+        }
+    }
+
+    if !created_paths.is_empty() {
+        println!("Removed the partially extracted files.");
+    }
+}
+
+/// [`set_extracted_metadata`] restores the permissions and modification time of an entry extracted from an archive.
+/// Failing to do so is only reported, since the contents of the cache are still usable.
+pub fn set_extracted_metadata(outpath: &Path, mode: Option<u32>, modified: Option<FileTime>) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Some(mode) = mode {
+            if fs::set_permissions(outpath, fs::Permissions::from_mode(mode)).is_err() {
+                eprintln!("Could not restore the permissions of {}.", outpath.display());
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        if mode.is_some_and(|mode| mode & 0o222 == 0) {
+            if let Ok(metadata) = fs::metadata(outpath) {
+                let mut permissions = metadata.permissions();
+                permissions.set_readonly(true);
+                let _ = fs::set_permissions(outpath, permissions);
+            }
+        }
+    }
+
+    if let Some(modified) = modified {
+        if filetime::set_file_mtime(outpath, modified).is_err() {
+            eprintln!("Could not restore the modification time of {}.", outpath.display());
+        }
+    }
+}
+
 
 /// [`del_restore_dir`] attempts to delete the `.cache` directory and returns an error
 /// message if it fails.
 /// 
 /// Returns:
 /// 
-/// [`del_restore_dir`] returns an error message if process fails.
-pub fn del_restore_dir() -> Result<(), &'static str>{
+/// [`del_restore_dir`] returns an error if process fails.
+pub fn del_restore_dir() -> Result<(), CacheError>{
     if !Path::new(".cache").exists(){
-        return Err("We could not find the .cache directory. This maybe due to it being already deleted in the meantime during execution. This should usually not happen.");
+        return Err(CacheError::invalid("We could not find the .cache directory. This maybe due to it being already deleted in the meantime during execution. This should usually not happen."));
     }
 
-    let remove_dir_result_err = fs::remove_dir_all(".cache").is_err();
+    let remove_dir_result = fs::remove_dir_all(".cache");
 
-    if remove_dir_result_err {
-        return Err("Failed removing .cache directory. This maybe due to the directory not being found or having too low permissions.")
+    if remove_dir_result.is_err() {
+        return Err(CacheError::io("Failed removing .cache directory. This maybe due to the directory not being found or having too low permissions.", remove_dir_result.unwrap_err()));
     }
 
     return Ok(());
 }
 
 
-/// [`del_webdav_cache`] deletes a file from a webdav server based on the operating system
-/// and branch name.
+/// [`del_remote_cache`] deletes the cache of the current operating system and branch from the storage backend.
+/// The digest and checksum files older versions stored next to the archive are deleted as well.
 /// 
 /// Arguments:
 /// 
-/// * `ostype`: type of [`enums::OsType`], which represents the Operating system that's currently being used.
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the cache is deleted from.
 /// 
 /// Returns:
 /// 
-/// The function [`del_webdav_cache`] returns an error message if process fails.
-pub fn del_webdav_cache(ostype: enums::OsType) -> Result<(), &'static str>{
+/// The function [`del_remote_cache`] returns an error if process fails.
+pub fn del_remote_cache(store: &dyn CacheStore) -> Result<(), CacheError>{
+    let archive_file_name = envfuncs::get_archive_file_name();
+    let delete_result = store.delete(storage::get_object_path(&archive_file_name).as_str());
 
-    if !Path::new(".cache").exists(){
-        return Err("We could not find a folder named .cache.");
-        
+    if delete_result.is_err(){
+        return delete_result;
+    }
+
+    if let Ok(digest_file) = digest::get_digest_file(){
+        let _ = fs::remove_file(digest_file);
+    }
+
+    for sidecar_path in [digest::get_remote_digest_path(&archive_file_name), digest::get_remote_checksum_path(&archive_file_name)]{
+        let delete_sidecar_result = store.delete(sidecar_path.as_str());
+
+        if delete_sidecar_result.as_ref().is_err_and(|error| !error.is_not_found()){
+            return delete_sidecar_result;
+        }
+    }
+
+    return Ok(());
+}
+
+/// [`list_remote_cache`] prints all caches of the current project that are stored on the storage backend.
+/// 
+/// Arguments:
+/// 
+/// * `store`: type of [`&dyn CacheStore`], the storage backend the caches are listed from.
+/// 
+/// Returns:
+/// 
+/// The function [`list_remote_cache`] returns an error if process fails.
+pub fn list_remote_cache(store: &dyn CacheStore) -> Result<(), CacheError>{
+    let list_result = store.list(storage::get_project_path().as_str());
+
+    if list_result.is_err(){
+        return Err(list_result.unwrap_err());
     }
 
-    let webdav_addr = envfuncs::get_webdavaddr();
-    let webdav_client = client::Client::init(&envfuncs::get_webdav_user(), &envfuncs::get_webdav_password());
-    let file_name = format!{"{}-{}.zip", ostype.to_string(), envfuncs::get_branch_name()};
-    let remove_result = webdav_client.delete(format!("{}/gitcache/{}/{}", webdav_addr, envfuncs::get_projectid(), file_name).as_str()).is_err();
+    // Digest files and the checksum files of older versions belong to their archive and are not listed as caches.
+    let cache_names: Vec<String> = list_result.unwrap()
+        .into_iter()
+        .map(|object| object.name)
+        .filter(|name| !name.ends_with(digest::DIGEST_EXTENSION) && !name.ends_with(digest::CHECKSUM_EXTENSION))
+        .collect();
+    println!("Found {} caches for project {} on the storage backend.", cache_names.len(), envfuncs::get_projectid());
 
-    if remove_result {
-        return Err("Encountered an error while removing the file from the webdav server");
+    for cache_name in cache_names{
+        println!("{}", cache_name);
     }
 
     return Ok(());
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::datas::{enums::ArchiveFormat, errors::CacheError};
+    use crate::storage::{self, memory::MemoryStore};
+    use crate::testutils::{self, Entry, TestEnv};
+    use crate::{create_cache, digest, envfuncs};
+
+    /// Archive entries trying to write outside of the directory they are extracted to.
+    const MALICIOUS_ENTRIES: [Entry; 6] = [
+        Entry::File("../evil.txt", "evil"),
+        Entry::File("/tmp/worker-cache-absolute.txt", "evil"),
+        Entry::File("project/file.txt", "ok"),
+        Entry::Link("project/up", "../../outside"),
+        Entry::Link("project/absolute", "/etc"),
+        Entry::Link("project/inner", "file.txt")
+    ];
+
+    /// Writes a fixture archive in `format` containing `entries` and extracts it into the directory `into`.
+    fn extract(test_env: &TestEnv, format: ArchiveFormat, entries: &[Entry], into: &Path, root: &Path) -> Result<(), CacheError> {
+        let archive = test_env.path().join(format!("fixture{}", format.extension()));
+        testutils::write_archive(&archive, format, entries);
+
+        return testutils::extract_archive(&archive, format, into, root);
+    }
+
+    #[test]
+    fn extraction_skips_entries_escaping_the_target() {
+        for format in ArchiveFormat::ALL {
+            let test_env = TestEnv::new();
+            let into = test_env.path().join("extracted");
+            fs::create_dir(&into).unwrap();
+
+            let extract_result = extract(&test_env, format, &MALICIOUS_ENTRIES, &into, &into);
+            assert!(extract_result.is_ok(), "{:?}: {}", format, extract_result.unwrap_err());
+
+            assert_eq!(testutils::read(&into.join("project/file.txt")), "ok");
+            assert!(fs::symlink_metadata(into.join("project/inner")).unwrap().file_type().is_symlink());
+            assert!(!test_env.path().join("evil.txt").exists());
+            assert!(!into.join("evil.txt").exists());
+            assert!(!Path::new("/tmp/worker-cache-absolute.txt").exists());
+            assert!(fs::symlink_metadata(into.join("project/up")).is_err(), "{:?}", format);
+            assert!(fs::symlink_metadata(into.join("project/absolute")).is_err(), "{:?}", format);
+            assert!(!test_env.path().join("outside").exists());
+        }
+    }
+
+    #[test]
+    fn extraction_does_not_write_through_existing_symlinks() {
+        for format in ArchiveFormat::ALL {
+            let test_env = TestEnv::new();
+            let into = test_env.path().join("extracted");
+            fs::create_dir_all(into.join("project")).unwrap();
+            fs::create_dir(test_env.path().join("outside")).unwrap();
+            super::create_symlink(&test_env.path().join("outside"), &into.join("project/up")).unwrap();
+
+            let entries = [Entry::File("project/up/written.txt", "evil"), Entry::File("project/file.txt", "ok")];
+            let extract_result = extract(&test_env, format, &entries, &into, &into);
+            assert!(extract_result.is_ok(), "{:?}: {}", format, extract_result.unwrap_err());
+
+            assert_eq!(testutils::read(&into.join("project/file.txt")), "ok");
+            assert!(!test_env.path().join("outside/written.txt").exists(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn extraction_keeps_links_when_the_project_dir_is_a_symlink() {
+        for format in ArchiveFormat::ALL {
+            let test_env = TestEnv::new();
+            let real_dir = test_env.path().join("real");
+            let project_dir = test_env.path().join("project");
+            fs::create_dir(&real_dir).unwrap();
+            super::create_symlink(&real_dir, &project_dir).unwrap();
+
+            // The roots are canonicalized, while the destinations are located below the symlink.
+            let entries = [
+                Entry::File("dir/file.txt", "ok"),
+                Entry::Link("dir/inner", "file.txt"),
+                Entry::Link("dir/up", "../dir/file.txt")
+            ];
+            let extract_result = extract(&test_env, format, &entries, &project_dir, &project_dir);
+            assert!(extract_result.is_ok(), "{:?}: {}", format, extract_result.unwrap_err());
+
+            assert_eq!(testutils::read(&real_dir.join("dir/inner")), "ok", "{:?}", format);
+            assert_eq!(testutils::read(&real_dir.join("dir/up")), "ok", "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn rm_remote_deletes_the_cache_without_a_cache_folder() {
+        let test_env = TestEnv::new();
+        let store = MemoryStore::new();
+        test_env.write("deps/a.txt", "a");
+        test_env.set("cachepath_deps", "deps");
+        assert!(create_cache::main(&store).is_ok());
+
+        let archive_path = storage::get_object_path(envfuncs::get_archive_file_name().as_str());
+        let checksum_path = digest::get_remote_checksum_path(envfuncs::get_archive_file_name().as_str());
+        store.insert(checksum_path.as_str(), b"checksum of an older version".to_vec());
+        assert!(!Path::new(".cache").exists());
+        assert!(super::list_remote_cache(&store).is_ok());
+
+        let delete_result = super::del_remote_cache(&store);
+
+        assert!(delete_result.is_ok(), "{}", delete_result.unwrap_err());
+        assert!(store.object(archive_path.as_str()).is_none());
+        assert!(store.object(checksum_path.as_str()).is_none());
+        assert!(store.object(digest::get_remote_digest_path(envfuncs::get_archive_file_name().as_str()).as_str()).is_none());
+        assert!(super::del_remote_cache(&store).is_err_and(|error| error.is_not_found()));
+    }
+}
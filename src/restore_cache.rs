@@ -1,54 +1,55 @@
-use std::{fs::{self, File}, path::Path, io::Read};
-use crate::{datas::{structs::{self, RestoreData}, enums}, helpers, envfuncs};
+use std::{collections::HashMap, fs::{self, File}, path::{Component, Path, PathBuf}, io::Read};
+use walkdir::WalkDir;
+use crate::{datas::{structs::{self, RestoreData}, enums, errors::CacheError}, globs, helpers, envfuncs, tar, zip};
 
 /// The main function restores the .cache folder to the locations indicated by the data.json file.
 /// 
 /// Returns:
-/// `main` returns an error code if process fails indicating where failure happened.
-pub fn main() -> Result<(), i32>{
+/// `main` returns the error if process fails.
+pub fn main() -> Result<(), CacheError>{
     println!("Welcome to the caching restore tool.
     \nThis will restore your .cache folder to the locations indicated by the data.json file.");
 
     if !Path::new(".cache").exists(){
-        eprintln!("We could not find the .cache file. Please ensure that the folder is at the correct location.");
-        return Err(51);
+        return Err(CacheError::invalid("We could not find the .cache folder. Please ensure that the folder is at the correct location."));
     }
 
     if !Path::new(".cache/data.json").exists(){
-        eprintln!("The .cache folder does not contain a data.json file. This is needed to restore the items to the locations they're supposed to be.");
-        return Err(52);
+        return Err(CacheError::invalid("The .cache folder does not contain a data.json file. This is needed to restore the items to the locations they're supposed to be."));
     }
 
-    let folders = envfuncs::get_env_if_startswith("cachepath_");
-    let files = envfuncs::get_env_if_startswith("cachefile_");
+    let restore_paths = get_restore_paths();
 
-    if (folders.len() == 0)  && (files.len() == 0){
-        eprintln!("Found 0 directories or files via searching env vars that start with cachepath_ or cachefile_. 
-        \nPlease make sure something is part of the cachepath_ or cachefile_ enviorement variables so we know what to restore.");
-        return Err(21);
+    if restore_paths.is_err(){
+        return Err(restore_paths.unwrap_err());
     }
 
+    let (folders, files) = restore_paths.unwrap();
+    let remap = envfuncs::get_path_remap();
+
+    if remap.is_err(){
+        return Err(remap.unwrap_err());
+    }
+
+    let remap = remap.unwrap();
     let json_file = File::open(".cache/data.json");
 
     if json_file.is_err(){
-        eprintln!("Could not read json file. This maybe because it's corrupt or we have invalid permissions. Please check your permissions and try again");
-        return Err(53);
+        return Err(CacheError::io("Could not read json file. This maybe because it's corrupt or we have invalid permissions. Please check your permissions and try again", json_file.unwrap_err()));
     }
 
     let mut json_file = json_file.unwrap();
     let mut data = String::new();
-    let data_read_is_err = json_file.read_to_string(&mut data).is_err();
+    let data_read_result = json_file.read_to_string(&mut data);
 
-    if data_read_is_err {
-        eprintln!("Could not read json file. This maybe because it's corrupt or we have invalid permissions. Please check your permissions and try again");
-        return Err(54);
+    if data_read_result.is_err() {
+        return Err(CacheError::io("Could not read json file. This maybe because it's corrupt or we have invalid permissions. Please check your permissions and try again", data_read_result.unwrap_err()));
     }
 
     let seralized_data = serde_json::from_str(data.as_str());
 
     if seralized_data.is_err(){
-        eprintln!("Encountered an issue while attempting to deserialize data.json file");
-        return Err(55);
+        return Err(CacheError::serde("Encountered an issue while attempting to deserialize data.json file", seralized_data.unwrap_err()));
     }
 
     let seralized_data: Vec<structs::RestoreData> = seralized_data.unwrap();
@@ -58,59 +59,226 @@ pub fn main() -> Result<(), i32>{
     }
 
     println!("Found .cache and data.json file both of which are valid. Starting restore process");
-    let restore_res = restore_data(seralized_data, folders, files);
+    let restore_res = restore_data(seralized_data, folders, files, &remap);
 
     if restore_res.is_err(){
-        eprintln!("Encountered an unrecoverable error during restore process. 
-        \nError was: {}", restore_res.unwrap_err());
-        return Err(56);
+        eprintln!("Encountered an unrecoverable error during restore process.");
+        return restore_res;
     }
 
     let del_restore_dir_res = helpers::del_restore_dir();
 
     if del_restore_dir_res.is_err(){
         eprintln!("Encountered an issue attempting to delete the restore directory. 
-        \nExiting with an error to prevent issues creating cache.
-        \nError was: {}", del_restore_dir_res.unwrap_err());
-        return Err(57);
+        \nExiting with an error to prevent issues creating cache.");
+        return del_restore_dir_res;
+    }
+
+    return Ok(());
+}
+
+/// [`restore_archive`] restores the objects straight from a downloaded archive to the locations indicated by it's data.json file,
+/// without extracting the archive into the .cache folder first. Every entry is written once, directly to it's destination.
+/// Objects are selected and validated the same way as by [`restore_data`], symlinks are validated while extracting them.
+///
+/// Arguments:
+///
+/// * `archive_file`: type of [`&Path`], the downloaded archive. The format is detected from it's first bytes.
+///
+/// Returns:
+/// An error if the archive could not be read or an entry could not be written.
+pub fn restore_archive(archive_file: &Path) -> Result<(), CacheError> {
+    println!("Restoring the objects straight from the archive to the locations indicated by it's data.json file.");
+
+    let restore_paths = get_restore_paths();
+
+    if restore_paths.is_err(){
+        return Err(restore_paths.unwrap_err());
+    }
+
+    let (folders, files) = restore_paths.unwrap();
+    let remap = envfuncs::get_path_remap();
+
+    if remap.is_err(){
+        return Err(remap.unwrap_err());
+    }
+
+    let remap = remap.unwrap();
+    let format = enums::ArchiveFormat::detect(archive_file);
+
+    if format.is_none(){
+        return Err(CacheError::invalid("Could not detect the format of the downloaded archive. The file may be corrupted or was not created by this tool."));
+    }
+
+    let format = format.unwrap();
+    let data = match format {
+        enums::ArchiveFormat::Zip => zip::read_data_file(archive_file),
+        _ => tar::read_data_file(archive_file, format)
+    };
+
+    if data.is_err(){
+        return Err(data.unwrap_err());
+    }
+
+    let seralized_data = serde_json::from_str(data.unwrap().as_str());
+
+    if seralized_data.is_err(){
+        return Err(CacheError::serde("Encountered an issue while attempting to deserialize data.json file", seralized_data.unwrap_err()));
+    }
+
+    let seralized_data: Vec<structs::RestoreData> = seralized_data.unwrap();
+
+    if seralized_data.is_empty(){
+        return Err(CacheError::invalid("The data.json file does not contain any objects to restore."));
+    }
+
+    let mut error_count = 0;
+    let restore_roots = envfuncs::get_restore_roots();
+    let mut destinations: HashMap<String, (PathBuf, enums::CacheType)> = HashMap::new();
+
+    for restore_obj in seralized_data.iter(){
+        if !is_selected(restore_obj, &folders, &files){
+            continue;
+        }
+
+        let validate_result = validate_restore_data(restore_obj, &restore_roots, &remap);
+
+        if validate_result.is_err() {
+            println!("Refusing to restore the object named: {} to {}", restore_obj.restore_obj_name, restore_obj.restore_to);
+            println!("The Error was:
+                    \n{}", validate_result.unwrap_err());
+            error_count = error_count +1;
+            continue;
+        }
+
+        destinations.insert(restore_obj.get_cache_name().to_string(), (validate_result.unwrap(), restore_obj.cachetype));
+    }
+
+    // Entries are stored as <cache name>/<path inside of the object>, entries of objects that are not restored are skipped.
+    let destination = |name: &Path| -> Option<PathBuf> {
+        let mut components = name.components();
+        let cache_name = components.next()?.as_os_str().to_str()?;
+        let (destination, cachetype) = destinations.get(cache_name)?;
+        let relative_path = components.as_path();
+
+        if relative_path.as_os_str().is_empty() {
+            return Some(destination.clone());
+        }
+
+        if *cachetype == enums::CacheType::File {
+            return None;
+        }
+
+        return Some(destination.join(relative_path));
+    };
+
+    let target = helpers::ExtractTarget { destination: &destination, roots: &restore_roots, check_links: true };
+    let extract_result = match format {
+        enums::ArchiveFormat::Zip => zip::unzip_file(archive_file, &target),
+        _ => tar::untar_file(archive_file, format, &target)
+    };
+
+    if extract_result.is_err(){
+        eprintln!("Encountered an unrecoverable error during restore process.");
+        return extract_result;
     }
 
+    for restore_obj in seralized_data.iter().filter(|restore_obj| destinations.contains_key(restore_obj.get_cache_name())){
+        let object_type = match restore_obj.cachetype {
+            enums::CacheType::Directory => "Folder",
+            enums::CacheType::File => "File"
+        };
+
+        println!("Restored {} named: {} to this location: {}", object_type, restore_obj.restore_obj_name, restore_obj.get_restore_to(&remap).display());
+    }
+
+    println!("Restored {} objects to all designated locations. Encountered {} Errors along the way.", destinations.len(), error_count);
     return Ok(());
 }
 
+/// The suffixes and values of the `cachepath_` or `cachefile_` enviorement variables (see [`envfuncs::get_env_with_suffix`]).
+type RestorePaths = Vec<(String, String)>;
+
+/// [`get_restore_paths`] reads the paths that should be restored from the `cachepath_` and `cachefile_` enviorement variables.
+///
+/// Returns:
+/// A tuple of the folder and file paths, together with the suffix of their variable, or a config error if neither are set.
+fn get_restore_paths() -> Result<(RestorePaths, RestorePaths), CacheError> {
+    let folders = envfuncs::get_env_with_suffix("cachepath_");
+    let files = envfuncs::get_env_with_suffix("cachefile_");
+
+    if (folders.len() == 0)  && (files.len() == 0){
+        return Err(CacheError::config("Found 0 directories or files via searching env vars that start with cachepath_ or cachefile_. 
+        \nPlease make sure something is part of the cachepath_ or cachefile_ enviorement variables so we know what to restore."));
+    }
+
+    return Ok((folders, files));
+}
+
+/// [`is_selected`] checks if an object of the data.json file is part of the `cachepath_` or `cachefile_` enviorement variables.
+/// Objects are matched by the suffix of the variable they were cached with, so the path of the project may differ between jobs.
+/// Objects that are not selected are skipped while restoring.
+fn is_selected(restore_obj: &RestoreData, restore_folder_paths: &[(String, String)], restore_file_paths: &[(String, String)]) -> bool {
+    let restore_paths = match restore_obj.cachetype {
+        enums::CacheType::Directory => restore_folder_paths,
+        enums::CacheType::File => restore_file_paths
+    };
+
+    if let Some(entry_name) = &restore_obj.entry_name {
+        return restore_paths.iter().any(|(restore_entry_name, _)| restore_entry_name == entry_name);
+    }
+
+    // data.json files of older versions don't contain the suffix, their objects are matched by their absolute path.
+    let restore_paths: Vec<String> = restore_paths.iter().map(|(_, restore_path)| restore_path.clone()).collect();
+    return globs::matches_any(&restore_paths, format!("{}/{}", restore_obj.restore_to, restore_obj.restore_obj_name).as_str());
+}
 
 /// `restore_data` takes in a [`Vec<RestoreData>`], as well as vectors of folder
 /// and file paths, and attempts to restore the data to the designated locations. 
-/// Compares the `res_data_vec` with `restore_folder_paths` and `restore_file_paths` to determine if we should restore the item or not.
+/// Compares the `res_data_vec` with `restore_folder_paths` and `restore_file_paths` to determine if we should restore the item or not
+/// (see [`is_selected`]).
 /// 
 /// Arguments:
 /// 
 /// * `res_data_vec`: type of [`Vec<RestoreData>`] which contains information
 /// about the data to be restored, such as the cache type (directory or file), the restore destination,
 /// and the name of the object to be restored.
-/// * `restore_folder_paths`: type of [`Vec<String>`] representing the paths to the folders where the data
-/// should be restored.
-/// * `restore_file_paths`: type of [`Vec<String>`] representing the paths to the files that need to be
-/// restored.
+/// * `restore_folder_paths`: type of [`Vec<(String, String)>`] representing the suffixes of the `cachepath_` variables
+/// and the paths to the folders where the data should be restored.
+/// * `restore_file_paths`: type of [`Vec<(String, String)>`] representing the suffixes of the `cachefile_` variables
+/// and the paths to the files that need to be restored.
+/// * `remap`: type of [`&[(String, String)]`], the pairs of `CACHE_PATH_REMAP` (see [`envfuncs::get_path_remap`]).
 /// 
 /// Returns:
-/// Returns an error message if we failed.
-pub fn restore_data(res_data_vec: Vec<RestoreData>, restore_folder_paths: Vec<String>, restore_file_paths: Vec<String>) -> Result<(), &'static str>{
+/// Returns an error if we failed.
+pub fn restore_data(res_data_vec: Vec<RestoreData>, restore_folder_paths: RestorePaths, restore_file_paths: RestorePaths, remap: &[(String, String)]) -> Result<(), CacheError>{
     if res_data_vec.is_empty(){
-        return Err("The array you entered is empty. Please give us an array with at least one function.");
+        return Err(CacheError::invalid("The data.json file does not contain any objects to restore."));
     }
 
     let mut error_count = 0;
+    let restore_roots = envfuncs::get_restore_roots();
 
     for restore_obj in res_data_vec.clone(){
+        //Check if our Item is in the enviorement variables. If not skip.
+        if !is_selected(&restore_obj, &restore_folder_paths, &restore_file_paths){
+            continue;
+        }
+
+        let validate_result = validate_restore_data(&restore_obj, &restore_roots, remap)
+            .and_then(|destination| validate_cached_links(&restore_obj, &destination, &restore_roots));
+
+        if validate_result.is_err() {
+            println!("Refusing to restore the object named: {} to {}", restore_obj.restore_obj_name, restore_obj.restore_to);
+            println!("The Error was:
+                    \n{}", validate_result.unwrap_err());
+            error_count = error_count +1;
+            continue;
+        }
+
         match restore_obj.cachetype{
             enums::CacheType::Directory => {     
-                //Check if our Item is in the enviorement variables. If not skip.
-                if !restore_folder_paths.contains(&format!("{}/{}", restore_obj.restore_to, restore_obj.restore_obj_name)){
-                    continue;
-                }            
-
-                let restore_file_res = restore_folder(restore_obj.clone());
+                let restore_file_res = restore_folder(restore_obj.clone(), remap);
 
                 if restore_file_res.is_err() {
                    println!("Encountered an error attempting to restore File named: {}", restore_obj.restore_obj_name);
@@ -120,13 +288,7 @@ pub fn restore_data(res_data_vec: Vec<RestoreData>, restore_folder_paths: Vec<St
                 }
             }
             enums::CacheType::File => {
-
-                //Check if our Item is in the enviorement variables. If not skip.
-                if !restore_file_paths.contains(&format!("{}/{}", restore_obj.restore_to, restore_obj.restore_obj_name)){
-                    continue;
-                }
-
-                 let restore_file_res = restore_file(restore_obj.clone());
+                 let restore_file_res = restore_file(restore_obj.clone(), remap);
 
                  if restore_file_res.is_err() {
                     println!("Encountered an error attempting to restore File named: {}", restore_obj.restore_obj_name);
@@ -144,37 +306,167 @@ pub fn restore_data(res_data_vec: Vec<RestoreData>, restore_folder_paths: Vec<St
 }
 
 
+/// [`validate_restore_data`] checks that an entry of the data.json file only restores below one of the allowed restore roots
+/// (see [`envfuncs::get_restore_roots`]), since the archive may have been tampered with on the storage backend.
+/// Entries are rejected if their name is not a plain file or folder name, if their location contains `..`,
+/// or if their location or destination resolves outside of the roots. The symlinks contained in the object are validated by
+/// [`validate_cached_links`] or while extracting them (see [`helpers::ExtractTarget`]).
+/// 
+/// Arguments:
+/// 
+/// * `restore_data`: type of [`&RestoreData`], the entry to validate.
+/// * `restore_roots`: type of [`&[PathBuf]`], the canonicalized directories the entry may be restored into.
+/// * `remap`: type of [`&[(String, String)]`], the pairs of `CACHE_PATH_REMAP` (see [`envfuncs::get_path_remap`]).
+/// 
+/// Returns:
+/// 
+/// [`validate_restore_data`] returns the canonicalized destination of the entry or an error if the entry must not be restored.
+pub fn validate_restore_data(restore_data: &RestoreData, restore_roots: &[PathBuf], remap: &[(String, String)]) -> Result<PathBuf, CacheError> {
+    let mut name_components = Path::new(&restore_data.restore_obj_name).components();
+
+    if !matches!((name_components.next(), name_components.next()), (Some(Component::Normal(_)), None)) {
+        return Err(CacheError::invalid("The name of the cached object is not a plain file or folder name."));
+    }
+
+    let mut cache_name_components = Path::new(restore_data.get_cache_name()).components();
+
+    if !matches!((cache_name_components.next(), cache_name_components.next()), (Some(Component::Normal(_)), None)) || restore_data.get_cache_name() == "data.json" {
+        return Err(CacheError::invalid("The name of the object inside of the .cache folder is not a plain file or folder name."));
+    }
+
+    let stored_restore_to = Path::new(&restore_data.restore_to);
+
+    if stored_restore_to.components().any(|component| component == Component::ParentDir) {
+        return Err(CacheError::invalid("The restore location contains .. components."));
+    }
+
+    let base_is_absolute = restore_data.base.map(|base| base == enums::PathBase::Absolute);
+
+    if base_is_absolute.is_some_and(|base_is_absolute| base_is_absolute != helpers::is_portable_absolute(&restore_data.restore_to)) {
+        return Err(CacheError::invalid("The restore location does not match it's base. Only locations with the Absolute base may be absolute paths."));
+    }
+
+    // The data.json file may have been tampered with, objects are only cached on known operating systems.
+    if restore_data.os == Some(enums::OsType::Unknown) {
+        return Err(CacheError::invalid("The object was cached on an unknown operating system."));
+    }
+
+    let current_os = enums::OsType::get_ostype();
+
+    if restore_data.os.is_some_and(|os| os != current_os) && !envfuncs::get_cross_os() {
+        return Err(CacheError::Invalid(format!("The object was cached on {} and this job runs on {}. Set CACHE_CROSS_OS=true to restore caches of other operating systems.",
+            restore_data.os.unwrap().to_string(), current_os.to_string())));
+    }
+
+    let restore_to = restore_data.get_restore_to(remap);
+
+    if !restore_to.is_absolute() {
+        return Err(CacheError::invalid("The restore location could not be resolved on this system. Absolute locations of other operating systems
+        have to be mapped via CACHE_PATH_REMAP and locations inside of the home directory need HOME or USERPROFILE to be set."));
+    }
+
+    let restore_to = fs::canonicalize(restore_to);
+
+    if restore_to.is_err() {
+        return Err(CacheError::io("We could not find the restore folder to restore this object into. The folder did not seem to exist. 
+        This maybe due to the operating system of this file being different and doesn't indicate a direct problem.", restore_to.unwrap_err()));
+    }
+
+    let restore_to = restore_to.unwrap();
+    let is_below_roots = |path: &Path| restore_roots.iter().any(|root| path.starts_with(root));
+
+    if !is_below_roots(&restore_to) {
+        return Err(CacheError::invalid("The restore location is outside of the allowed restore roots (the project directory, the home directory and CACHE_RESTORE_ROOTS)."));
+    }
+
+    let destination = restore_to.join(&restore_data.restore_obj_name);
+
+    if fs::symlink_metadata(&destination).is_ok_and(|metadata| metadata.file_type().is_symlink())
+        && !fs::canonicalize(&destination).is_ok_and(|resolved| is_below_roots(&resolved)) {
+        return Err(CacheError::invalid("The restore destination is a symlink pointing outside of the allowed restore roots."));
+    }
+
+    return Ok(destination);
+}
+
+/// [`validate_cached_links`] checks that the object extracted into the .cache folder does not contain a symlink
+/// pointing outside of the allowed restore roots once it is restored to `destination`.
+/// 
+/// Arguments:
+/// 
+/// * `restore_data`: type of [`&RestoreData`], the entry to validate.
+/// * `destination`: type of [`&Path`], the destination returned by [`validate_restore_data`].
+/// * `restore_roots`: type of [`&[PathBuf]`], the canonicalized directories the entry may be restored into.
+/// 
+/// Returns:
+/// 
+/// [`validate_cached_links`] returns an error if the entry must not be restored.
+fn validate_cached_links(restore_data: &RestoreData, destination: &Path, restore_roots: &[PathBuf]) -> Result<(), CacheError> {
+    let is_below_roots = |path: &Path| restore_roots.iter().any(|root| path.starts_with(root));
+    let source = Path::new(".cache").join(restore_data.get_cache_name());
+
+    for entry in WalkDir::new(&source).follow_links(false).follow_root_links(false) {
+        if entry.is_err() {
+            return Err(CacheError::io("Could not read the cached object to validate it's contents.", entry.unwrap_err().into()));
+        }
+
+        let entry = entry.unwrap();
+
+        if !entry.file_type().is_symlink() {
+            continue;
+        }
+
+        let link_target = fs::read_link(entry.path());
+
+        if link_target.is_err() {
+            return Err(CacheError::io("Could not read a symlink contained in the cached object.", link_target.unwrap_err()));
+        }
+
+        // Resolve the link as it will be located after restoring, relative links are resolved from the directory of the link.
+        let relative_path = entry.path().strip_prefix(&source).unwrap_or(Path::new(""));
+        let restored_link = helpers::normalize_path(&destination.join(relative_path));
+        let resolved_target = helpers::normalize_path(&restored_link.parent().unwrap_or(destination).join(link_target.unwrap()));
+
+        if !is_below_roots(&resolved_target) {
+            return Err(CacheError::invalid("The cached object contains a symlink pointing outside of the allowed restore roots."));
+        }
+    }
+
+    return Ok(());
+}
+
 /// [`restore_folder`] function restores a folder from the .cache directory to the location indicated by the `restore_data` object.
 /// 
 /// Arguments:
 /// 
 /// * `restore_data`: type of [`RestoreData`], which tells us which folder to restore and where to restore it to
+/// * `remap`: type of [`&[(String, String)]`], the pairs of `CACHE_PATH_REMAP` (see [`envfuncs::get_path_remap`]).
 /// 
 /// Returns:
 /// 
-/// [`restore_file`] returns an error message if restore process fails.
-pub fn restore_folder(restore_data: RestoreData) -> Result<(), &'static str> {
+/// [`restore_file`] returns an error if restore process fails.
+pub fn restore_folder(restore_data: RestoreData, remap: &[(String, String)]) -> Result<(), CacheError> {
     if restore_data.cachetype != enums::CacheType::Directory{
-        return Err("Attempted to copy invalid restore type. Please make sure restore types match");
+        return Err(CacheError::invalid("Attempted to copy invalid restore type. Please make sure restore types match"));
     }
 
-    let restore_dir = restore_data.restore_to.as_str();
+    let restore_dir = restore_data.get_restore_to(remap);
 
-    if !Path::new(restore_dir).exists(){
-        return Err("We could not find the restore folder to restore this object into. The folder did not seem to exist. 
-        This maybe due to the operating system of this file being different and doesn't indicate a direct problem.");
+    if !restore_dir.exists(){
+        return Err(CacheError::invalid("We could not find the restore folder to restore this object into. The folder did not seem to exist. 
+        This maybe due to the operating system of this file being different and doesn't indicate a direct problem."));
     }
 
-    let copy_from = format!(".cache/{}", restore_data.restore_obj_name);
-    let copy_to = format!("{}/{}", restore_data.restore_to, restore_data.restore_obj_name);
-    let copy_res_is_err = helpers::copy_recursively(copy_from, copy_to).is_err();
+    let copy_from = format!(".cache/{}", restore_data.get_cache_name());
+    let copy_to = restore_dir.join(&restore_data.restore_obj_name);
+    let copy_res = helpers::copy_recursively(copy_from, copy_to, &|_| false);
 
-    if copy_res_is_err {
-        return Err("Encounted an error while attempting to restore directory. 
-        This maybe due to insufficient permissions or because the directory was not at the expected location to copy from.");
+    if copy_res.is_err() {
+        return Err(CacheError::io("Encounted an error while attempting to restore directory. 
+        This maybe due to insufficient permissions or because the directory was not at the expected location to copy from.", copy_res.unwrap_err()));
     }
 
-    println!("Restored Folder named: {} to this location: {}", restore_data.restore_obj_name, restore_data.restore_to);
+    println!("Restored Folder named: {} to this location: {}", restore_data.restore_obj_name, restore_dir.display());
     return Ok(());
 }
 
@@ -183,31 +475,132 @@ pub fn restore_folder(restore_data: RestoreData) -> Result<(), &'static str> {
 /// Arguments:
 /// 
 /// * `restore_data`: type of [`RestoreData`], which tells us which object to restore and where to restore it to
+/// * `remap`: type of [`&[(String, String)]`], the pairs of `CACHE_PATH_REMAP` (see [`envfuncs::get_path_remap`]).
 /// 
 /// Returns:
 /// 
-/// [`restore_file`] returns an error message if restore process fails.
-pub fn restore_file(restore_data: RestoreData) -> Result<(), &'static str> {
+/// [`restore_file`] returns an error if restore process fails.
+pub fn restore_file(restore_data: RestoreData, remap: &[(String, String)]) -> Result<(), CacheError> {
     if restore_data.cachetype != enums::CacheType::File{
-        return Err("Attempted to copy invalid restore type. Please make sure restore types match");
+        return Err(CacheError::invalid("Attempted to copy invalid restore type. Please make sure restore types match"));
     }
 
-    let restore_dir = restore_data.restore_to.as_str();
+    let restore_dir = restore_data.get_restore_to(remap);
 
-    if !Path::new(restore_dir).exists(){
-        return Err("We could not find the restore folder to restore this object into. The folder did not seem to exist. 
-        This maybe due to the operating system of this file being different and doesn't indicate a direct problem.");
+    if !restore_dir.exists(){
+        return Err(CacheError::invalid("We could not find the restore folder to restore this object into. The folder did not seem to exist. 
+        This maybe due to the operating system of this file being different and doesn't indicate a direct problem."));
     }
 
-    let copy_from = format!(".cache/{}", restore_data.restore_obj_name);
-    let copy_to = format!("{}/{}", restore_data.restore_to, restore_data.restore_obj_name);
-    let copy_is_err = fs::copy(copy_from,copy_to).is_err();
+    let copy_from = format!(".cache/{}", restore_data.get_cache_name());
+    let copy_to = restore_dir.join(&restore_data.restore_obj_name);
+    let copy_res = helpers::copy_file(copy_from,copy_to);
 
-    if copy_is_err {
-        return Err("Encounted an error while attempting to copy the folder. 
-        This maybe due to insufficient permissions or because the file was not at the expected location to copy from.");
+    if copy_res.is_err() {
+        return Err(CacheError::io("Encounted an error while attempting to copy the file. 
+        This maybe due to insufficient permissions or because the file was not at the expected location to copy from.", copy_res.unwrap_err()));
     }
     
-    println!("Restored File named: {} to this location: {}", restore_data.restore_obj_name, restore_data.restore_to);
+    println!("Restored File named: {} to this location: {}", restore_data.restore_obj_name, restore_dir.display());
     return Ok(());
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::validate_restore_data;
+    use crate::datas::{enums, structs::RestoreData};
+    use crate::envfuncs;
+    use crate::testutils::TestEnv;
+
+    /// Creates an entry of the data.json file restoring the folder `name` into `restore_to`.
+    fn restore_data(name: &str, restore_to: &str, base: enums::PathBase) -> RestoreData {
+        return RestoreData {
+            restore_obj_name: name.to_string(),
+            cachetype: enums::CacheType::Directory,
+            restore_to: restore_to.to_string(),
+            base: Some(base),
+            cache_name: None,
+            entry_name: Some(String::from("deps")),
+            os: Some(enums::OsType::get_ostype())
+        };
+    }
+
+    #[test]
+    fn validate_accepts_entries_inside_of_the_project() {
+        let test_env = TestEnv::new();
+        fs::create_dir(test_env.path().join("sub")).unwrap();
+
+        let destination = validate_restore_data(&restore_data("deps", "sub", enums::PathBase::Project), &envfuncs::get_restore_roots(), &[]);
+        assert_eq!(destination.unwrap(), test_env.path().join("sub/deps"));
+    }
+
+    #[test]
+    fn validate_refuses_entries_escaping_the_roots() {
+        let test_env = TestEnv::new();
+        let roots = envfuncs::get_restore_roots();
+        let escaping_entries = [
+            restore_data("deps", "..", enums::PathBase::Project),
+            restore_data("deps", "sub/../..", enums::PathBase::Project),
+            restore_data("..", "", enums::PathBase::Project),
+            restore_data("a/b", "", enums::PathBase::Project),
+            restore_data("deps", "/", enums::PathBase::Absolute),
+            restore_data("deps", "/", enums::PathBase::Project)
+        ];
+
+        for restore_obj in escaping_entries {
+            let validate_result = validate_restore_data(&restore_obj, &roots, &[]);
+            assert!(validate_result.is_err(), "{:?}", restore_obj);
+            assert_eq!(validate_result.unwrap_err().exit_code(), 8, "{:?}", restore_obj);
+        }
+
+        // A destination that is a symlink pointing outside of the roots is refused as well.
+        crate::helpers::create_symlink(test_env.path().parent().unwrap(), &test_env.path().join("deps")).unwrap();
+        assert!(validate_restore_data(&restore_data("deps", "", enums::PathBase::Project), &roots, &[]).is_err());
+    }
+
+    #[test]
+    fn validate_maps_locations_of_other_operating_systems() {
+        let test_env = TestEnv::new();
+        fs::create_dir(test_env.path().join("sub")).unwrap();
+        test_env.set("CACHE_PATH_REMAP", format!("C:\\Users\\ci={}", test_env.path().display()).as_str());
+        let remap = envfuncs::get_path_remap().unwrap();
+        let restore_obj = restore_data("deps", "C:/Users/ci/sub", enums::PathBase::Absolute);
+
+        let destination = validate_restore_data(&restore_obj, &envfuncs::get_restore_roots(), &remap);
+
+        assert_eq!(destination.unwrap(), test_env.path().join("sub/deps"));
+        assert!(validate_restore_data(&restore_obj, &envfuncs::get_restore_roots(), &[]).is_err());
+    }
+
+    #[test]
+    fn invalid_path_remaps_are_config_errors() {
+        let test_env = TestEnv::new();
+        test_env.set("CACHE_PATH_REMAP", "C:\\Users\\ci=relative");
+
+        let remap = envfuncs::get_path_remap();
+
+        assert!(remap.is_err());
+        assert_eq!(remap.unwrap_err().exit_code(), 2);
+    }
+
+    #[test]
+    fn restore_refuses_objects_of_unknown_operating_systems() {
+        let test_env = TestEnv::new();
+        test_env.set("cachepath_deps", "deps");
+        test_env.set("CACHE_CROSS_OS", "true");
+        test_env.write(".cache/deps/a.txt", "a");
+        test_env.write(".cache/data.json", r#"[{"restore_obj_name":"deps","cachetype":"Directory","restore_to":"","base":"Project","entry_name":"deps","os":"Unknown"}]"#);
+
+        let restore_result = super::main();
+
+        assert!(restore_result.is_ok(), "{}", restore_result.unwrap_err());
+        assert!(!test_env.path().join("deps").exists());
+
+        test_env.set("CACHE_CROSS_OS", "false");
+        let restore_obj: RestoreData = serde_json::from_str(r#"{"restore_obj_name":"deps","cachetype":"Directory","restore_to":"","os":"Unknown"}"#).unwrap();
+        let validate_result = validate_restore_data(&restore_obj, &envfuncs::get_restore_roots(), &[]);
+
+        assert_eq!(validate_result.unwrap_err().exit_code(), 8);
+    }
+}
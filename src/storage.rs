@@ -0,0 +1,227 @@
+pub mod filesystem;
+pub mod ftp;
+#[cfg(test)]
+pub mod memory;
+pub mod s3;
+pub mod webdav;
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::datas::enums;
+use crate::datas::errors::CacheError;
+use crate::envfuncs;
+
+/// [`CacheStore`] is implemented by every storage backend the cache can be stored on.
+/// All paths handed to a store are relative to the root of the backend (e.g. `gitcache/<project>/<name>.zip`),
+/// the backend is responsible for turning them into addresses it understands.
+/// Objects are streamed from and to local files, so the size of a cache is never limited by the available memory.
+pub trait CacheStore {
+    /// Uploads the contents of `local_file` to `path`, replacing an already existing object.
+    /// The object is replaced atomically: until the upload is complete readers get the old object, and if the upload fails
+    /// the old object is left untouched. Backends without atomic uploads write to a temporary object (see [`get_temp_path`]) first.
+    fn put(&self, local_file: &Path, path: &str) -> Result<(), CacheError>;
+
+    /// Downloads the object at `path` into `local_file`. Returns [`CacheError::NotFound`] if the object does not exist,
+    /// in that case `local_file` is not created.
+    fn get(&self, path: &str, local_file: &Path) -> Result<(), CacheError>;
+
+    /// Deletes the object at `path`.
+    fn delete(&self, path: &str) -> Result<(), CacheError>;
+
+    /// Lists all objects directly inside of the directory at `path`, together with the time they were last modified.
+    fn list(&self, path: &str) -> Result<Vec<StoredObject>, CacheError>;
+
+    /// Checks if an object or directory exists at `path`.
+    fn exists(&self, path: &str) -> Result<bool, CacheError>;
+
+    /// Creates the directory at `path`. Does not fail if the directory already exists.
+    fn mkdir(&self, path: &str) -> Result<(), CacheError>;
+}
+
+/// [`StoredObject`] is an entry of a directory on the storage backend, as returned by [`CacheStore::list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredObject {
+    /// The name of the object inside of the listed directory.
+    pub name: String,
+    /// The time the object was last modified. [`None`] if the backend does not report it, e.g. for directories.
+    pub modified: Option<SystemTime>
+}
+
+impl StoredObject {
+    /// Creates a [`StoredObject`] named `name`.
+    pub fn new(name: &str, modified: Option<SystemTime>) -> StoredObject {
+        return StoredObject { name: name.to_string(), modified };
+    }
+}
+
+/// [`get_cache_store`] creates the storage backend the cache should be stored on.
+/// The backend is selected via the `CACHE_BACKEND` enviorement variable and defaults to webdav.
+///
+/// Returns:
+///
+/// A [`Box<dyn CacheStore>`] of the configured backend.
+pub fn get_cache_store() -> Box<dyn CacheStore> {
+    match envfuncs::get_cache_backend(){
+        enums::BackendType::WebDav => { return Box::new(webdav::WebDavStore::from_env()); }
+        enums::BackendType::Ftp => { return Box::new(ftp::FtpStore::from_env()); }
+        enums::BackendType::S3 => { return Box::new(s3::S3Store::from_env()); }
+        enums::BackendType::FileSystem => { return Box::new(filesystem::FileSystemStore::from_env()); }
+    }
+}
+
+/// [`get_project_path`] returns the directory on the storage backend that contains the caches of the current project.
+///
+/// Returns:
+///
+/// A [`String`] in the format of `gitcache/<CI_PROJECT_ID>`.
+pub fn get_project_path() -> String {
+    return format!("gitcache/{}", envfuncs::get_projectid());
+}
+
+/// [`get_object_path`] returns the path of an object inside of the project directory on the storage backend.
+///
+/// Arguments:
+///
+/// * `name`: type of [`&str`], the name of the object (e.g. `Unix-main.zip`).
+///
+/// Returns:
+///
+/// A [`String`] in the format of `gitcache/<CI_PROJECT_ID>/<name>`.
+pub fn get_object_path(name: &str) -> String {
+    return format!("{}/{}", get_project_path(), name);
+}
+
+/// Marker contained in the name of objects that are still being uploaded.
+pub const TEMP_MARKER: &str = ".tmp-";
+
+/// [`get_temp_path`] returns a unique temporary path next to `path`, which an object is uploaded to before it is moved to `path`.
+/// The name contains the process id and the current time, so concurrent jobs uploading the same cache never share a temporary object.
+///
+/// Arguments:
+///
+/// * `path`: type of [`&str`], the final path of the object (e.g. `gitcache/<project>/<name>.zip`).
+///
+/// Returns:
+///
+/// A [`String`] in the format of `<path>.tmp-<pid>-<nanos>`.
+pub fn get_temp_path(path: &str) -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_nanos()).unwrap_or_default();
+    return format!("{}{}{}-{}", path, TEMP_MARKER, process::id(), nanos);
+}
+
+/// [`copy_to_local_file`] streams the contents of `reader` into `local_file`. A partially written file is removed if copying fails.
+///
+/// Arguments:
+///
+/// * `reader`: type of [`Read`], the downloaded contents of an object.
+/// * `local_file`: type of [`&Path`], the file the contents are written to. Overwritten if it already exists.
+///
+/// Returns:
+///
+/// An error if the file could not be written.
+pub fn copy_to_local_file(reader: &mut dyn Read, local_file: &Path) -> Result<(), CacheError> {
+    let file = File::create(local_file);
+
+    if file.is_err(){
+        return Err(CacheError::io("Encountered an error while attempting to create file. This maybe due to insufficient permissions.", file.unwrap_err()));
+    }
+
+    let mut writer = BufWriter::new(file.unwrap());
+    let copy_result = io::copy(reader, &mut writer).and_then(|_| writer.flush());
+
+    if copy_result.is_err(){
+        let _ = fs::remove_file(local_file);
+        return Err(CacheError::io("Encountered an error while writing the downloaded bytes to the file. This may indicate a full disk, insufficient permissions or an interrupted download.", copy_result.unwrap_err()));
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+    use std::path::Path;
+
+    use super::{copy_to_local_file, filesystem::FileSystemStore, CacheStore};
+    use crate::testutils::{self, TestEnv};
+
+    /// The most memory moving an archive may take, far below the size of the archive.
+    const MEMORY_CAP: usize = 4 * 1024 * 1024;
+    /// Size of the archive that is moved.
+    const ARCHIVE_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// Writes [`ARCHIVE_SIZE`] bytes of varying data to `path`.
+    fn write_archive(path: &Path) {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        let chunk: Vec<u8> = (0..1024 * 1024).map(|index| (index % 251) as u8).collect();
+
+        for index in 0..ARCHIVE_SIZE / chunk.len() as u64 {
+            writer.write_all(&[index as u8]).unwrap();
+            writer.write_all(&chunk[1..]).unwrap();
+        }
+
+        writer.flush().unwrap();
+    }
+
+    /// Compares the contents of two files without reading them into memory.
+    fn same_contents(first: &Path, second: &Path) -> bool {
+        let (mut first, mut second) = (BufReader::new(File::open(first).unwrap()), BufReader::new(File::open(second).unwrap()));
+
+        loop {
+            let (first_chunk, second_chunk) = (first.fill_buf().unwrap().to_vec(), second.fill_buf().unwrap().to_vec());
+            let length = first_chunk.len().min(second_chunk.len());
+
+            if first_chunk[..length] != second_chunk[..length] || (length == 0 && first_chunk.len() != second_chunk.len()) {
+                return false;
+            }
+
+            if length == 0 {
+                return true;
+            }
+
+            first.consume(length);
+            second.consume(length);
+        }
+    }
+
+    #[test]
+    fn moves_archives_larger_than_the_memory_cap() {
+        let test_env = TestEnv::new();
+        std::fs::create_dir_all(test_env.path().join("store/gitcache/42")).unwrap();
+        test_env.set("FSROOT", test_env.path().join("store").to_str().unwrap());
+        let store = FileSystemStore::from_env();
+        let archive = test_env.path().join("Unix-main.zip");
+        let downloaded = test_env.path().join("downloaded.zip");
+        write_archive(&archive);
+
+        let (put_result, put_peak) = testutils::peak_allocation(|| store.put(&archive, "gitcache/42/Unix-main.zip"));
+        assert!(put_result.is_ok(), "{}", put_result.unwrap_err());
+
+        let (get_result, get_peak) = testutils::peak_allocation(|| store.get("gitcache/42/Unix-main.zip", &downloaded));
+        assert!(get_result.is_ok(), "{}", get_result.unwrap_err());
+
+        assert_eq!(std::fs::metadata(&downloaded).unwrap().len(), ARCHIVE_SIZE);
+        assert!(same_contents(&downloaded, &archive));
+        assert!(put_peak < MEMORY_CAP, "Uploading took {} bytes of memory.", put_peak);
+        assert!(get_peak < MEMORY_CAP, "Downloading took {} bytes of memory.", get_peak);
+    }
+
+    #[test]
+    fn downloads_stream_larger_than_the_memory_cap() {
+        let test_env = TestEnv::new();
+        let downloaded = test_env.path().join("downloaded.zip");
+        // Simulates a http or ftp response body that is larger than the memory cap.
+        let mut response = io::repeat(7).take(ARCHIVE_SIZE);
+
+        let (copy_result, peak) = testutils::peak_allocation(|| copy_to_local_file(&mut response, &downloaded));
+
+        assert!(copy_result.is_ok(), "{}", copy_result.unwrap_err());
+        assert_eq!(std::fs::metadata(&downloaded).unwrap().len(), ARCHIVE_SIZE);
+        assert!(peak < MEMORY_CAP, "Downloading took {} bytes of memory.", peak);
+    }
+}
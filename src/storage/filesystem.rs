@@ -0,0 +1,211 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::datas::errors::CacheError;
+use crate::envfuncs;
+use crate::storage::{self, CacheStore, StoredObject};
+
+/// [`FileSystemStore`] stores the cache in a directory of the local file system, e.g. a shared NFS mount.
+/// Files are written to a temporary file next to their destination first and renamed afterwards,
+/// so concurrent jobs never read a partially written archive.
+pub struct FileSystemStore {
+    root: PathBuf
+}
+
+impl FileSystemStore {
+    /// Creates a [`FileSystemStore`] using the `FSROOT` enviorement variable.
+    ///
+    /// Returns:
+    /// A [`FileSystemStore`] storing it's data below the configured root directory.
+    pub fn from_env() -> FileSystemStore {
+        return FileSystemStore {
+            root: PathBuf::from(envfuncs::get_fs_root())
+        };
+    }
+
+    /// Builds the full path of `path` below the root directory.
+    fn full_path(&self, path: &str) -> PathBuf {
+        return self.root.join(path.trim_matches('/'));
+    }
+}
+
+impl CacheStore for FileSystemStore {
+    fn put(&self, local_file: &Path, path: &str) -> Result<(), CacheError> {
+        let destination = self.full_path(path);
+        let temp_path = self.full_path(storage::get_temp_path(path).as_str());
+
+        let source_file = fs::File::open(local_file);
+
+        if source_file.is_err(){
+            return Err(CacheError::io("Could not open file to read bytes into stream This maybe due to the archive having been deleted since creation.", source_file.unwrap_err()));
+        }
+
+        let temp_file = fs::File::create(&temp_path);
+
+        if temp_file.is_err(){
+            return Err(CacheError::io("Could not create temporary file in the cache directory. Please make sure the directory exists and we have write permissions.", temp_file.unwrap_err()));
+        }
+
+        let mut temp_file = temp_file.unwrap();
+
+        let copy_result = io::copy(&mut source_file.unwrap(), &mut temp_file).and_then(|_| temp_file.sync_all());
+
+        if copy_result.is_err(){
+            let _ = fs::remove_file(&temp_path);
+            return Err(CacheError::io("Encountered an error while writing the cache to the temporary file. This may indicate a full disk or insufficient permissions.", copy_result.unwrap_err()));
+        }
+
+        drop(temp_file);
+
+        let rename_result = fs::rename(&temp_path, &destination);
+
+        if rename_result.is_err(){
+            let _ = fs::remove_file(&temp_path);
+            return Err(CacheError::io("Could not move the temporary file to it's final location in the cache directory.", rename_result.unwrap_err()));
+        }
+
+        return Ok(());
+    }
+
+    fn get(&self, path: &str, local_file: &Path) -> Result<(), CacheError> {
+        let source_file = fs::File::open(self.full_path(path));
+
+        if let Err(open_error) = source_file {
+            if open_error.kind() == ErrorKind::NotFound {
+                return Err(CacheError::NotFound);
+            }
+
+            return Err(CacheError::io("Encountered an error while reading the cache file from the cache directory.", open_error));
+        }
+
+        return storage::copy_to_local_file(&mut source_file.unwrap(), local_file);
+    }
+
+    fn delete(&self, path: &str) -> Result<(), CacheError> {
+        let remove_result = fs::remove_file(self.full_path(path));
+
+        if let Err(remove_error) = remove_result {
+            if remove_error.kind() == ErrorKind::NotFound {
+                return Err(CacheError::NotFound);
+            }
+
+            return Err(CacheError::io("Encountered an error while removing the file from the cache directory.", remove_error));
+        }
+
+        return Ok(());
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<StoredObject>, CacheError> {
+        let read_dir_result = fs::read_dir(self.full_path(path));
+
+        if let Err(read_dir_error) = read_dir_result {
+            if read_dir_error.kind() == ErrorKind::NotFound {
+                return Err(CacheError::NotFound);
+            }
+
+            return Err(CacheError::io("Encountered a problem trying to read the cache directory.", read_dir_error));
+        }
+
+        let objects = read_dir_result.unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_name().to_string_lossy().contains(storage::TEMP_MARKER))
+            .map(|entry| StoredObject::new(entry.file_name().to_string_lossy().as_ref(), entry.metadata().and_then(|metadata| metadata.modified()).ok()))
+            .collect();
+
+        return Ok(objects);
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, CacheError> {
+        return Ok(self.full_path(path).exists());
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), CacheError> {
+        let create_result = fs::create_dir_all(self.full_path(path));
+
+        if create_result.is_err(){
+            return Err(CacheError::io("Could not create directory in the cache directory. Please make sure we have write permissions.", create_result.unwrap_err()));
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::FileSystemStore;
+    use crate::storage::{CacheStore, StoredObject};
+    use crate::testutils::{self, TestEnv};
+
+    /// Creates a [`FileSystemStore`] with the root `store` inside of the test directory.
+    fn create_store(test_env: &TestEnv) -> FileSystemStore {
+        fs::create_dir(test_env.path().join("store")).unwrap();
+        return FileSystemStore { root: test_env.path().join("store") };
+    }
+
+    #[test]
+    fn put_replaces_objects_by_renaming_a_temporary_file() {
+        let test_env = TestEnv::new();
+        let store = create_store(&test_env);
+        store.mkdir("gitcache/42").unwrap();
+
+        store.put(&test_env.write("first.zip", "first"), "gitcache/42/Unix-main.zip").unwrap();
+        store.put(&test_env.write("second.zip", "second"), "gitcache/42/Unix-main.zip").unwrap();
+
+        assert_eq!(testutils::read(&test_env.path().join("store/gitcache/42/Unix-main.zip")), "second");
+        assert_eq!(fs::read_dir(test_env.path().join("store/gitcache/42")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_put_leaves_no_partial_file() {
+        let test_env = TestEnv::new();
+        let store = create_store(&test_env);
+        store.mkdir("gitcache/42").unwrap();
+        store.put(&test_env.write("first.zip", "first"), "gitcache/42/Unix-main.zip").unwrap();
+
+        // A directory can be opened but not read, so the upload fails after the temporary file was created.
+        fs::create_dir(test_env.path().join("unreadable.zip")).unwrap();
+        let put_result = store.put(&test_env.path().join("unreadable.zip"), "gitcache/42/Unix-main.zip");
+
+        assert_eq!(put_result.unwrap_err().exit_code(), 4);
+        assert_eq!(testutils::read(&test_env.path().join("store/gitcache/42/Unix-main.zip")), "first");
+        assert_eq!(fs::read_dir(test_env.path().join("store/gitcache/42")).unwrap().count(), 1);
+
+        assert!(store.put(&test_env.path().join("missing.zip"), "gitcache/42/Unix-main.zip").is_err());
+        assert!(store.put(&test_env.path().join("first.zip"), "gitcache/missing/Unix-main.zip").is_err());
+        assert_eq!(fs::read_dir(test_env.path().join("store/gitcache")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn missing_objects_are_not_found() {
+        let test_env = TestEnv::new();
+        let store = create_store(&test_env);
+        store.mkdir("gitcache/42").unwrap();
+
+        assert!(store.get("gitcache/42/Unix-main.zip", &test_env.path().join("download.zip")).is_err_and(|error| error.is_not_found()));
+        assert!(!test_env.path().join("download.zip").exists());
+        assert!(store.delete("gitcache/42/Unix-main.zip").is_err_and(|error| error.is_not_found()));
+        assert!(store.list("gitcache/missing").is_err_and(|error| error.is_not_found()));
+        assert!(!store.exists("gitcache/42/Unix-main.zip").unwrap());
+    }
+
+    #[test]
+    fn objects_round_trip() {
+        let test_env = TestEnv::new();
+        let store = create_store(&test_env);
+        store.mkdir("gitcache/42").unwrap();
+        store.put(&test_env.write("cache.zip", "cache"), "gitcache/42/Unix-main.zip").unwrap();
+
+        assert!(store.exists("gitcache/42/Unix-main.zip").unwrap());
+        let modified = fs::metadata(test_env.path().join("store/gitcache/42/Unix-main.zip")).unwrap().modified().unwrap();
+        assert_eq!(store.list("gitcache/42").unwrap(), vec![StoredObject::new("Unix-main.zip", Some(modified))]);
+
+        store.get("gitcache/42/Unix-main.zip", &test_env.path().join("download.zip")).unwrap();
+        assert_eq!(testutils::read(&test_env.path().join("download.zip")), "cache");
+
+        store.delete("gitcache/42/Unix-main.zip").unwrap();
+        assert!(store.list("gitcache/42").unwrap().is_empty());
+    }
+}
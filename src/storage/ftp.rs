@@ -0,0 +1,416 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::time::SystemTime;
+
+use suppaftp::native_tls::TlsConnector;
+use suppaftp::types::FileType;
+use suppaftp::{FtpError, NativeTlsConnector, NativeTlsFtpStream, Status};
+
+use crate::datas::errors::CacheError;
+use crate::envfuncs;
+use crate::storage::{self, CacheStore, StoredObject};
+
+/// [`FtpStore`] stores the cache on a ftp server. If FTPS is enabled the connection is secured via explicit TLS.
+/// Uploads are written to a temporary file first and renamed to their final name afterwards.
+pub struct FtpStore {
+    address: String,
+    domain: String,
+    user: String,
+    password: String,
+    secure: bool
+}
+
+impl FtpStore {
+    /// Creates a [`FtpStore`] using the `FTPADDR`, `FTPUSER`, `FTPPASS` and `FTPS` enviorement variables.
+    ///
+    /// Returns:
+    /// A [`FtpStore`] for the configured server. The connection is only opened once an operation is executed.
+    pub fn from_env() -> FtpStore {
+        let address = envfuncs::get_ftp_addr();
+        let address = address.trim_start_matches("ftps://").trim_start_matches("ftp://").trim_end_matches('/').to_string();
+        let domain = address.split(':').next().unwrap_or_default().to_string();
+        let address = if address.contains(':') { address } else { format!("{}:21", address) };
+
+        return FtpStore {
+            address,
+            domain,
+            user: envfuncs::get_ftp_user(),
+            password: envfuncs::get_ftp_password(),
+            secure: envfuncs::get_ftps_enabled()
+        };
+    }
+
+    /// Opens and authenticates a new connection to the ftp server.
+    ///
+    /// Returns:
+    /// A logged in [`NativeTlsFtpStream`] using binary transfers or an error if connecting failed.
+    fn connect(&self) -> Result<NativeTlsFtpStream, CacheError> {
+        let ftp_stream = NativeTlsFtpStream::connect(self.address.as_str());
+
+        if ftp_stream.is_err(){
+            return Err(CacheError::ftp("Could not connect to the ftp server. Please make sure FTPADDR is correct and the server is reachable.", ftp_stream.unwrap_err()));
+        }
+
+        let mut ftp_stream = ftp_stream.unwrap();
+
+        if self.secure {
+            let tls_connector = TlsConnector::new();
+
+            if tls_connector.is_err(){
+                return Err(CacheError::ftp("Could not initialize TLS to secure the ftp connection.", FtpError::SecureError(tls_connector.unwrap_err().to_string())));
+            }
+
+            let secure_stream = ftp_stream.into_secure(NativeTlsConnector::from(tls_connector.unwrap()), self.domain.as_str());
+
+            if secure_stream.is_err(){
+                return Err(CacheError::ftp("Could not secure the connection to the ftp server. Please make sure the server supports explicit FTPS.", secure_stream.unwrap_err()));
+            }
+
+            ftp_stream = secure_stream.unwrap();
+        }
+
+        let login_result = ftp_stream.login(self.user.as_str(), self.password.as_str());
+
+        if login_result.is_err(){
+            return Err(CacheError::ftp("Could not log in to the ftp server. Please ensure your access credentials are correct.", login_result.unwrap_err()));
+        }
+
+        let transfer_type_result = ftp_stream.transfer_type(FileType::Binary);
+
+        if transfer_type_result.is_err(){
+            return Err(CacheError::ftp("Could not switch the ftp connection to binary transfer mode.", transfer_type_result.unwrap_err()));
+        }
+
+        return Ok(ftp_stream);
+    }
+
+    /// Runs `operation` on a fresh connection and closes the connection afterwards.
+    fn with_connection<T>(&self, operation: impl FnOnce(&mut NativeTlsFtpStream) -> Result<T, CacheError>) -> Result<T, CacheError> {
+        let ftp_stream = self.connect();
+
+        if ftp_stream.is_err(){
+            return Err(ftp_stream.unwrap_err());
+        }
+
+        let mut ftp_stream = ftp_stream.unwrap();
+        let result = operation(&mut ftp_stream);
+        let _ = ftp_stream.quit();
+
+        return result;
+    }
+}
+
+/// Checks if an [`FtpError`] was caused by the file not being available on the server.
+fn is_not_found(error: &FtpError) -> bool {
+    match error {
+        FtpError::UnexpectedResponse(response) => { return response.status == Status::FileUnavailable; }
+        _ => { return false; }
+    }
+}
+
+impl CacheStore for FtpStore {
+    fn put(&self, local_file: &Path, path: &str) -> Result<(), CacheError> {
+        let file = File::open(local_file);
+
+        if file.is_err(){
+            return Err(CacheError::io("Could not open file to read bytes into stream This maybe due to the archive having been deleted since creation.", file.unwrap_err()));
+        }
+
+        let mut reader = BufReader::new(file.unwrap());
+
+        let temp_path = storage::get_temp_path(path);
+
+        return self.with_connection(|ftp_stream| {
+            let upload_result = ftp_stream.put_file(temp_path.as_str(), &mut reader);
+
+            if upload_result.is_err(){
+                let _ = ftp_stream.rm(temp_path.as_str());
+                return Err(CacheError::ftp("Encountered an error while attempting to upload the archive.", upload_result.unwrap_err()));
+            }
+
+            let rename_result = ftp_stream.rename(temp_path.as_str(), path);
+
+            if rename_result.is_err(){
+                let _ = ftp_stream.rm(temp_path.as_str());
+                return Err(CacheError::ftp("Could not rename the uploaded archive to it's final name. Please make sure the server allows replacing existing files.", rename_result.unwrap_err()));
+            }
+
+            return Ok(());
+        });
+    }
+
+    fn get(&self, path: &str, local_file: &Path) -> Result<(), CacheError> {
+        return self.with_connection(|ftp_stream| {
+            let mut copy_result = Ok(());
+            let download_result = ftp_stream.retr(path, |reader| {
+                copy_result = storage::copy_to_local_file(reader, local_file);
+                return Ok(());
+            });
+
+            if let Err(download_error) = download_result {
+                let _ = fs::remove_file(local_file);
+
+                if is_not_found(&download_error){
+                    return Err(CacheError::NotFound);
+                }
+
+                return Err(CacheError::ftp("Encountered an error downloading cache file from server.", download_error));
+            }
+
+            return copy_result;
+        });
+    }
+
+    fn delete(&self, path: &str) -> Result<(), CacheError> {
+        return self.with_connection(|ftp_stream| {
+            let remove_result = ftp_stream.rm(path);
+
+            if let Err(remove_error) = remove_result {
+                if is_not_found(&remove_error){
+                    return Err(CacheError::NotFound);
+                }
+
+                return Err(CacheError::ftp("Encountered an error while removing the file from the ftp server.", remove_error));
+            }
+
+            return Ok(());
+        });
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<StoredObject>, CacheError> {
+        return self.with_connection(|ftp_stream| {
+            let list_result = ftp_stream.nlst(Some(path));
+
+            if let Err(list_error) = list_result {
+                if is_not_found(&list_error){
+                    return Err(CacheError::NotFound);
+                }
+
+                return Err(CacheError::ftp("Encountered a problem trying to retrieve ftp folder structure.", list_error));
+            }
+
+            // Depending on the server NLST either returns plain names or the full path of each entry.
+            let names: Vec<String> = list_result.unwrap()
+                .iter()
+                .filter_map(|entry| entry.trim_end_matches('/').rsplit('/').next().map(|name| name.to_string()))
+                .filter(|name| !name.is_empty() && name != "." && name != ".." && !name.contains(storage::TEMP_MARKER))
+                .collect();
+
+            // NLST only returns names, the modification time is requested per object. Directories have none.
+            let objects = names.iter()
+                .map(|name| {
+                    let modified = ftp_stream.mdtm(format!("{}/{}", path.trim_end_matches('/'), name))
+                        .ok()
+                        .map(|modified| SystemTime::from(modified.and_utc()));
+                    return StoredObject::new(name, modified);
+                })
+                .collect();
+
+            return Ok(objects);
+        });
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, CacheError> {
+        let path = path.trim_matches('/');
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (Some(parent), name),
+            None => (None, path)
+        };
+
+        return self.with_connection(|ftp_stream| {
+            let list_result = ftp_stream.nlst(parent);
+
+            if let Err(list_error) = list_result {
+                // Some servers answer with FileUnavailable when listing a directory that doesn't exist.
+                if is_not_found(&list_error){
+                    return Ok(false);
+                }
+
+                return Err(CacheError::ftp("Encountered a problem trying to retrieve ftp folder structure.", list_error));
+            }
+
+            return Ok(list_result.unwrap().iter().any(|entry| entry.trim_end_matches('/').rsplit('/').next() == Some(name)));
+        });
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), CacheError> {
+        let exists_result = self.exists(path);
+
+        if exists_result.is_err(){
+            return Err(exists_result.unwrap_err());
+        }
+
+        if exists_result.unwrap(){
+            return Ok(());
+        }
+
+        return self.with_connection(|ftp_stream| {
+            let mkdir_result = ftp_stream.mkdir(path);
+
+            if mkdir_result.is_err(){
+                return Err(CacheError::ftp("Could not make directory on the ftp server. Please make sure you have the ability to create directories.", mkdir_result.unwrap_err()));
+            }
+
+            println!("Folder {} was created on the server.", path);
+            return Ok(());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::{fs, thread};
+
+    use super::FtpStore;
+    use crate::storage::{CacheStore, StoredObject};
+    use crate::testutils::TestEnv;
+
+    /// Files and directories stored by the [`start_server`] stand-in.
+    #[derive(Default)]
+    struct ServerState {
+        files: BTreeMap<String, Vec<u8>>,
+        dirs: BTreeSet<String>
+    }
+
+    /// Returns the directory containing `path`, the root is an empty string.
+    fn parent_of(path: &str) -> &str {
+        return path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default();
+    }
+
+    /// Starts a minimal ftp server on a random local port that supports the commands used by [`FtpStore`] in passive mode.
+    /// Like real servers it refuses to create files or directories whose parent directory does not exist.
+    ///
+    /// Returns:
+    /// The address of the server.
+    fn start_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let state = Arc::new(Mutex::new(ServerState::default()));
+
+        thread::spawn(move || {
+            for control in listener.incoming().flatten() {
+                let state = state.clone();
+                thread::spawn(move || handle_connection(control, &state));
+            }
+        });
+
+        return address;
+    }
+
+    /// Answers the commands of a single control connection until the client quits.
+    fn handle_connection(mut control: TcpStream, state: &Mutex<ServerState>) {
+        let mut reader = BufReader::new(control.try_clone().unwrap());
+        let mut passive: Option<TcpListener> = None;
+        let mut rename_from: Option<String> = None;
+        control.write_all(b"220 stand-in ready\r\n").unwrap();
+
+        loop {
+            let mut line = String::new();
+
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+
+            let line = line.trim_end();
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let path = argument.trim_matches('/').to_string();
+            let mut state = state.lock().unwrap();
+
+            let reply = match command {
+                "USER" => String::from("331 password required"),
+                "PASS" if argument == "secret" => String::from("230 logged in"),
+                "PASS" => String::from("530 login incorrect"),
+                "TYPE" => String::from("200 type set"),
+                "PASV" => {
+                    let data_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                    let port = data_listener.local_addr().unwrap().port();
+                    passive = Some(data_listener);
+                    format!("227 Entering Passive Mode (127,0,0,1,{},{})", port >> 8, port & 0xff)
+                }
+                "STOR" if state.dirs.contains(parent_of(&path)) || parent_of(&path).is_empty() => {
+                    control.write_all(b"150 receiving\r\n").unwrap();
+                    let mut contents = Vec::new();
+                    passive.take().unwrap().accept().unwrap().0.read_to_end(&mut contents).unwrap();
+                    state.files.insert(path, contents);
+                    String::from("226 stored")
+                }
+                "RETR" if state.files.contains_key(&path) => {
+                    control.write_all(b"150 sending\r\n").unwrap();
+                    passive.take().unwrap().accept().unwrap().0.write_all(&state.files[&path]).unwrap();
+                    String::from("226 sent")
+                }
+                "NLST" if state.dirs.contains(&path) || path.is_empty() => {
+                    control.write_all(b"150 listing\r\n").unwrap();
+                    let names: String = state.files.keys().chain(state.dirs.iter())
+                        .filter(|child| parent_of(child) == path)
+                        .map(|child| format!("{}\r\n", child))
+                        .collect();
+                    passive.take().unwrap().accept().unwrap().0.write_all(names.as_bytes()).unwrap();
+                    String::from("226 listed")
+                }
+                "MDTM" if state.files.contains_key(&path) => String::from("213 20261017120000"),
+                "DELE" if state.files.remove(&path).is_some() => String::from("250 deleted"),
+                "MKD" if !state.dirs.contains(&path) && (state.dirs.contains(parent_of(&path)) || parent_of(&path).is_empty()) => {
+                    state.dirs.insert(path.clone());
+                    format!("257 \"{}\" created", path)
+                }
+                "RNFR" if state.files.contains_key(&path) => {
+                    rename_from = Some(path);
+                    String::from("350 ready for destination")
+                }
+                "RNTO" if rename_from.is_some() => {
+                    let contents = state.files.remove(&rename_from.take().unwrap()).unwrap();
+                    state.files.insert(path, contents);
+                    String::from("250 renamed")
+                }
+                "QUIT" => {
+                    control.write_all(b"221 bye\r\n").unwrap();
+                    return;
+                }
+                "STOR" | "RETR" | "NLST" | "MDTM" | "DELE" | "MKD" | "RNFR" => String::from("550 file unavailable"),
+                _ => String::from("502 not implemented")
+            };
+
+            control.write_all(format!("{}\r\n", reply).as_bytes()).unwrap();
+        }
+    }
+
+    #[test]
+    fn ftp_store_round_trip() {
+        let test_env = TestEnv::new();
+        test_env.set("FTPADDR", format!("ftp://{}", start_server()).as_str());
+        test_env.set("FTPUSER", "ci");
+        test_env.set("FTPPASS", "secret");
+        let store = FtpStore::from_env();
+        let archive = test_env.write("Unix-main.zip", "archive contents");
+
+        assert!(!store.exists("gitcache").unwrap());
+        assert!(store.mkdir("gitcache").is_ok());
+        assert!(store.mkdir("gitcache/42").is_ok());
+        assert!(store.mkdir("gitcache/42").is_ok());
+        assert!(store.exists("gitcache/42").unwrap());
+
+        let put_result = store.put(&archive, "gitcache/42/Unix-main.zip");
+        assert!(put_result.is_ok(), "{}", put_result.unwrap_err());
+        let modified = chrono::DateTime::parse_from_rfc3339("2026-10-17T12:00:00Z").unwrap().into();
+        assert_eq!(store.list("gitcache/42").unwrap(), vec![StoredObject::new("Unix-main.zip", Some(modified))]);
+        assert!(store.exists("gitcache/42/Unix-main.zip").unwrap());
+
+        let downloaded = test_env.path().join("downloaded.zip");
+        assert!(store.get("gitcache/42/Unix-main.zip", &downloaded).is_ok());
+        assert_eq!(fs::read_to_string(&downloaded).unwrap(), "archive contents");
+        assert!(store.get("gitcache/42/Unix-other.zip", &test_env.path().join("missing.zip")).is_err_and(|error| error.is_not_found()));
+        assert!(!test_env.path().join("missing.zip").exists());
+
+        assert!(store.delete("gitcache/42/Unix-main.zip").is_ok());
+        assert!(store.list("gitcache/42").unwrap().is_empty());
+        assert!(store.delete("gitcache/42/Unix-main.zip").is_err_and(|error| error.is_not_found()));
+        assert!(store.list("gitcache/43").is_err_and(|error| error.is_not_found()));
+        assert!(store.put(&archive, "gitcache/43/Unix-main.zip").is_err());
+    }
+}
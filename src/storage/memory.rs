@@ -0,0 +1,122 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::datas::errors::CacheError;
+use crate::storage::{self, CacheStore, StoredObject};
+
+/// [`MemoryStore`] keeps all objects in memory. It is used to test the commands without a storage server.
+/// Like webdav and ftp servers it only creates a directory if it's parent exists and refuses to store objects in a missing directory.
+/// Objects are modified one second after each other, so the order they were stored in doesn't depend on the resolution of the clock.
+#[derive(Default)]
+pub struct MemoryStore {
+    objects: RefCell<BTreeMap<String, Vec<u8>>>,
+    modified: RefCell<BTreeMap<String, SystemTime>>,
+    dirs: RefCell<BTreeSet<String>>,
+    clock: Cell<u64>
+}
+
+impl MemoryStore {
+    /// Creates an empty [`MemoryStore`].
+    pub fn new() -> MemoryStore {
+        return MemoryStore::default();
+    }
+
+    /// Returns the contents of the object at `path`.
+    pub fn object(&self, path: &str) -> Option<Vec<u8>> {
+        return self.objects.borrow().get(path.trim_matches('/')).cloned();
+    }
+
+    /// Stores `contents` at `path` without any checks, e.g. to replace an archive with a tampered one.
+    pub fn insert(&self, path: &str, contents: Vec<u8>) {
+        self.clock.set(self.clock.get() + 1);
+        self.modified.borrow_mut().insert(path.trim_matches('/').to_string(), SystemTime::UNIX_EPOCH + Duration::from_secs(self.clock.get()));
+        self.objects.borrow_mut().insert(path.trim_matches('/').to_string(), contents);
+    }
+
+    /// Checks if the directory at `path` exists. The root always exists.
+    fn dir_exists(&self, path: &str) -> bool {
+        return path.is_empty() || self.dirs.borrow().contains(path);
+    }
+}
+
+/// Returns the directory containing `path`, the root is an empty string.
+fn parent_of(path: &str) -> &str {
+    return path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default();
+}
+
+impl CacheStore for MemoryStore {
+    fn put(&self, local_file: &Path, path: &str) -> Result<(), CacheError> {
+        let path = path.trim_matches('/');
+
+        if !self.dir_exists(parent_of(path)) {
+            return Err(CacheError::io("Could not upload the object since it's directory does not exist.", io::Error::from(ErrorKind::NotFound)));
+        }
+
+        let contents = fs::read(local_file);
+
+        if contents.is_err(){
+            return Err(CacheError::io("Could not read the file to upload.", contents.unwrap_err()));
+        }
+
+        self.insert(path, contents.unwrap());
+        return Ok(());
+    }
+
+    fn get(&self, path: &str, local_file: &Path) -> Result<(), CacheError> {
+        let contents = self.object(path);
+
+        if contents.is_none(){
+            return Err(CacheError::NotFound);
+        }
+
+        return storage::copy_to_local_file(&mut contents.unwrap().as_slice(), local_file);
+    }
+
+    fn delete(&self, path: &str) -> Result<(), CacheError> {
+        if self.objects.borrow_mut().remove(path.trim_matches('/')).is_none(){
+            return Err(CacheError::NotFound);
+        }
+
+        self.modified.borrow_mut().remove(path.trim_matches('/'));
+
+        return Ok(());
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<StoredObject>, CacheError> {
+        let path = path.trim_matches('/');
+
+        if !self.dir_exists(path) {
+            return Err(CacheError::NotFound);
+        }
+
+        let objects = self.objects.borrow();
+        let dirs = self.dirs.borrow();
+        let modified = self.modified.borrow();
+        let stored_objects = objects.keys().chain(dirs.iter())
+            .filter(|child| parent_of(child) == path && !child.is_empty() && !child.contains(storage::TEMP_MARKER))
+            .map(|child| StoredObject::new(child.rsplit('/').next().unwrap_or_default(), modified.get(child.as_str()).copied()))
+            .collect();
+
+        return Ok(stored_objects);
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, CacheError> {
+        let path = path.trim_matches('/');
+        return Ok(self.dir_exists(path) || self.objects.borrow().contains_key(path));
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), CacheError> {
+        let path = path.trim_matches('/');
+
+        if !self.dir_exists(parent_of(path)) {
+            return Err(CacheError::io("Could not create the directory since it's parent does not exist.", io::Error::from(ErrorKind::NotFound)));
+        }
+
+        self.dirs.borrow_mut().insert(path.to_string());
+        return Ok(());
+    }
+}
@@ -0,0 +1,385 @@
+use std::fs::File;
+use std::path::Path;
+use std::time::SystemTime;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::Method;
+use rustydav::client::Client;
+
+use crate::datas::errors::CacheError;
+use crate::envfuncs;
+use crate::storage::{self, CacheStore, StoredObject};
+
+/// Characters that are percent-encoded in a segment of an url. Only the unreserved characters of RFC 3986 are kept,
+/// so keys containing e.g. spaces, `#`, `%` or `?` address the right file.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// [`WebDavStore`] stores the cache on a webdav server.
+/// Uploads are written to a temporary file first and moved to their final location afterwards (see [`WebDavStore::move_object`]).
+pub struct WebDavStore {
+    client: Client,
+    // rustydav does not allow setting the Overwrite header of MOVE requests, so they are sent via reqwest directly.
+    http_client: reqwest::blocking::Client,
+    address: String,
+    user: String,
+    password: String
+}
+
+impl WebDavStore {
+    /// Creates a [`WebDavStore`] using the `WEBDAVADDR`, `WEBDAVUSER` and `WEBDAVPASS` enviorement variables.
+    ///
+    /// Returns:
+    /// A [`WebDavStore`] connected to the configured server.
+    pub fn from_env() -> WebDavStore {
+        let user = envfuncs::get_webdav_user();
+        let password = envfuncs::get_webdav_password();
+
+        return WebDavStore {
+            client: Client::init(&user, &password),
+            http_client: reqwest::blocking::Client::new(),
+            address: envfuncs::get_webdavaddr(),
+            user,
+            password
+        };
+    }
+
+    /// Builds the full url of `path` on the webdav server. Every segment of `path` is percent-encoded.
+    fn url(&self, path: &str) -> String {
+        let encoded_path: Vec<String> = path.trim_start_matches('/').split('/')
+            .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+            .collect();
+
+        return format!("{}/{}", self.address.trim_end_matches('/'), encoded_path.join("/"));
+    }
+
+    /// [`WebDavStore::move_object`] moves the file at `from` to `to` via a MOVE request. An existing file at `to` is replaced,
+    /// webdav servers do this atomically so readers either get the old or the new file.
+    ///
+    /// Arguments:
+    ///
+    /// * `from`: type of [`&str`], the path of the file to move.
+    /// * `to`: type of [`&str`], the path the file is moved to.
+    ///
+    /// Returns:
+    /// An error if the file could not be moved.
+    fn move_object(&self, from: &str, to: &str) -> Result<(), CacheError> {
+        let move_result = self.http_client.request(Method::from_bytes(b"MOVE").unwrap(), self.url(from).as_str())
+            .basic_auth(self.user.as_str(), Some(self.password.as_str()))
+            .header("Destination", self.url(to))
+            .header("Overwrite", "T")
+            .send();
+
+        if move_result.is_err(){
+            return Err(CacheError::http("Encountered an error while attempting to move the uploaded archive to it's final location.", move_result.unwrap_err()));
+        }
+
+        match move_result.unwrap().status(){
+            http::StatusCode::CREATED | http::StatusCode::NO_CONTENT | http::StatusCode::OK => { return Ok(()); }
+            status @ http::StatusCode::FORBIDDEN => {
+                return Err(CacheError::http_status("Could not move the uploaded archive to it's final location.
+                Please make sure you have the ability to move and overwrite files on the webdav server.", status));
+            }
+            status @ http::StatusCode::PRECONDITION_FAILED => {
+                return Err(CacheError::http_status("The webdav server refused to overwrite the existing archive.", status));
+            }
+            status => {
+                return Err(CacheError::http_status("Response contained unknown / unhandled status code while moving the uploaded archive.", status));
+            }
+        }
+    }
+}
+
+impl CacheStore for WebDavStore {
+    fn put(&self, local_file: &Path, path: &str) -> Result<(), CacheError> {
+        let file = File::open(local_file);
+
+        if file.is_err(){
+            return Err(CacheError::io("Could not open file to read bytes into stream This maybe due to the archive having been deleted since creation.", file.unwrap_err()));
+        }
+
+        // Passing the file as body streams it to the server instead of reading it into memory.
+        let temp_path = storage::get_temp_path(path);
+        let upload_result = self.client.put(file.unwrap(), self.url(&temp_path).as_str());
+
+        if upload_result.is_err(){
+            let _ = self.client.delete(self.url(&temp_path).as_str());
+            return Err(CacheError::http("Encountered an error while attempting to upload the archive.", upload_result.unwrap_err()));
+        }
+
+        match upload_result.unwrap().status(){
+            http::StatusCode::ACCEPTED | http::StatusCode::OK | http::StatusCode::CREATED | http::StatusCode::NO_CONTENT => {}
+            status @ http::StatusCode::FORBIDDEN => {
+                return Err(CacheError::http_status("Could not upload the file.
+                Please make sure you have the ability to create and upload files on the webdav server.", status));
+            }
+            status => {
+                let _ = self.client.delete(self.url(&temp_path).as_str());
+                return Err(CacheError::http_status("Response contained unknown / unhandled status code while uploading the file.", status));
+            }
+        }
+
+        let move_result = self.move_object(&temp_path, path);
+
+        if move_result.is_err(){
+            let _ = self.client.delete(self.url(&temp_path).as_str());
+            return move_result;
+        }
+
+        return Ok(());
+    }
+
+    fn get(&self, path: &str, local_file: &Path) -> Result<(), CacheError> {
+        let download_result = self.client.get(self.url(path).as_str());
+
+        if download_result.is_err() {
+            return Err(CacheError::http("Encountered an error downloading cache file from server.", download_result.unwrap_err()));
+        }
+
+        let mut download_result = download_result.unwrap();
+
+        match download_result.status(){
+            http::StatusCode::ACCEPTED | http::StatusCode::OK | http::StatusCode::CREATED => {}
+            http::StatusCode::NOT_FOUND => {
+                return Err(CacheError::NotFound);
+            }
+            status @ http::StatusCode::FORBIDDEN => {
+                return Err(CacheError::http_status("Could not download the file.
+                Please make sure you have the ability to read files on the webdav server.", status));
+            }
+            status => {
+                return Err(CacheError::http_status("Response contained unknown / unhandled status code while downloading the file.", status));
+            }
+        }
+
+        return storage::copy_to_local_file(&mut download_result, local_file);
+    }
+
+    fn delete(&self, path: &str) -> Result<(), CacheError> {
+        let remove_result = self.client.delete(self.url(path).as_str());
+
+        if remove_result.is_err() {
+            return Err(CacheError::http("Encountered an error while removing the file from the webdav server.", remove_result.unwrap_err()));
+        }
+
+        match remove_result.unwrap().status(){
+            http::StatusCode::NOT_FOUND => { return Err(CacheError::NotFound); }
+            status if status.is_success() => { return Ok(()); }
+            status => { return Err(CacheError::http_status("Response contained unknown / unhandled status code while removing the file.", status)); }
+        }
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<StoredObject>, CacheError> {
+        let list_result = self.client.list(self.url(path).as_str(), "1");
+
+        if list_result.is_err() {
+            return Err(CacheError::http("Encountered a problem trying to retrieve webdav folder structure.", list_result.unwrap_err()));
+        }
+
+        let list_result = list_result.unwrap();
+
+        match list_result.status(){
+            http::StatusCode::MULTI_STATUS | http::StatusCode::OK => {}
+            http::StatusCode::NOT_FOUND => { return Err(CacheError::NotFound); }
+            status => { return Err(CacheError::http_status("Response contained unknown / unhandled status code while listing the folder.", status)); }
+        }
+
+        let body_text = list_result.text();
+
+        if body_text.is_err(){
+            return Err(CacheError::http("Could not read message body when attempting to fetch folder structure.", body_text.unwrap_err()));
+        }
+
+        // The response contains the listed folder itself as well, it's href ends with the segments of `path`.
+        let own_segments: Vec<String> = path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| segment.to_string())
+            .collect();
+        let objects = parse_responses(body_text.unwrap().as_str())
+            .into_iter()
+            .filter(|(segments, _)| !segments.ends_with(&own_segments))
+            .filter_map(|(segments, modified)| segments.last().map(|name| StoredObject::new(name, modified)))
+            .filter(|object| !object.name.contains(storage::TEMP_MARKER))
+            .collect();
+
+        return Ok(objects);
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, CacheError> {
+        let list_result = self.client.list(self.url(path).as_str(), "0");
+
+        if list_result.is_err() {
+            return Err(CacheError::http("Encountered a problem trying to retrieve webdav folder structure.", list_result.unwrap_err()));
+        }
+
+        match list_result.unwrap().status(){
+            http::StatusCode::MULTI_STATUS | http::StatusCode::OK | http::StatusCode::FOUND => { return Ok(true); }
+            http::StatusCode::NOT_FOUND => { return Ok(false); }
+            status @ http::StatusCode::FORBIDDEN => {
+                return Err(CacheError::http_status("Could not check the folder structure. Please make sure you have read permissions on the webdav server.", status));
+            }
+            status => { return Err(CacheError::http_status("Response contained unknown / unhandled status code while checking the folder structure.", status)); }
+        }
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), CacheError> {
+        let make_dir_result = self.client.mkcol(self.url(path).as_str());
+
+        if make_dir_result.is_err() {
+            return Err(CacheError::http("Could not make directory. Please ensure your access credentials are correct.", make_dir_result.unwrap_err()));
+        }
+
+        match make_dir_result.unwrap().status(){
+            http::StatusCode::ACCEPTED | http::StatusCode::OK | http::StatusCode::CREATED => {
+                println!("Folder {} was created on the server.", path);
+            }
+            // Webdav servers answer with METHOD_NOT_ALLOWED if the collection already exists.
+            http::StatusCode::METHOD_NOT_ALLOWED => {}
+            status @ http::StatusCode::FORBIDDEN => {
+                return Err(CacheError::http_status("Could not create a folder.
+                Please make sure you have the ability to create and upload files on the webdav server.", status));
+            }
+            status @ http::StatusCode::CONFLICT => {
+                return Err(CacheError::http_status("Could not create the folder. This usually means that a parent folder is missing
+                or that we have insufficient read permissions inside of it.", status));
+            }
+            status => {
+                return Err(CacheError::http_status("Response contained unknown / unhandled status code while creating a folder.", status));
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// [`parse_responses`] extracts the `href` and `getlastmodified` elements of every response of a PROPFIND response.
+/// The element names are matched ignoring the case and namespace prefix. The hrefs are unescaped and split into their segments,
+/// which are percent-decoded afterwards, so an encoded `/` (`%2F`) stays part of it's segment.
+///
+/// Arguments:
+///
+/// * `body`: type of [`&str`], the xml body returned by the webdav server.
+///
+/// Returns:
+///
+/// A [`Vec`] containing the decoded, non empty segments of every `href` element and the modification time of the response it belongs to.
+fn parse_responses(body: &str) -> Vec<(Vec<String>, Option<SystemTime>)> {
+    let hrefs = find_elements(body, "href");
+    let modification_times = find_elements(body, "getlastmodified");
+    let mut responses: Vec<(Vec<String>, Option<SystemTime>)> = Vec::new();
+
+    // The href is the first element of a response, so the properties of a response are found between it's href and the next one.
+    for (index, (position, href)) in hrefs.iter().enumerate() {
+        let next_position = hrefs.get(index + 1).map(|(next_position, _)| *next_position).unwrap_or(body.len());
+        let modified = modification_times.iter()
+            .find(|(modified_position, _)| modified_position > position && *modified_position < next_position)
+            .and_then(|(_, modified)| chrono::DateTime::parse_from_rfc2822(modified).ok())
+            .map(SystemTime::from);
+
+        responses.push((decode_href(href), modified));
+    }
+
+    return responses;
+}
+
+/// [`find_elements`] returns the position and the trimmed, non empty text of every element named `name` in the xml `body`.
+fn find_elements<'a>(body: &'a str, name: &str) -> Vec<(usize, &'a str)> {
+    let mut elements: Vec<(usize, &str)> = Vec::new();
+    let needle = format!("{}>", name);
+    let mut search_from = 0;
+
+    // Only ascii characters are matched, so every position found is a char boundary of the original body.
+    while let Some(tag_start) = find_ignore_ascii_case(body, search_from, needle.as_str()) {
+        let start = tag_start + needle.len();
+        let end = match body[start..].find('<') {
+            Some(end) => start + end,
+            None => break
+        };
+
+        // Closing tags look like </D:href> as well, those contain no value. The element name has to end at a prefix or the tag start.
+        let open = body[..tag_start].rfind('<');
+        let is_opening_tag = open.is_some_and(|open| !body[open..].starts_with("</")
+            && matches!(body.as_bytes()[tag_start - 1], b'<' | b':'));
+
+        if is_opening_tag {
+            let value = body[start..end].trim();
+            if !value.is_empty() {
+                elements.push((tag_start, value));
+            }
+        }
+
+        search_from = end;
+    }
+
+    return elements;
+}
+
+/// Returns the byte position of the first occurence of the ascii string `needle` in `haystack` after `from`, ignoring the case.
+fn find_ignore_ascii_case(haystack: &str, from: usize, needle: &str) -> Option<usize> {
+    return haystack.as_bytes()[from..]
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+        .map(|position| from + position);
+}
+
+/// Unescapes the xml entities of an href, splits it into it's segments and decodes their percent encoded characters (e.g. `%20` for a space).
+fn decode_href(href: &str) -> Vec<String> {
+    let unescaped = href.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+
+    return unescaped.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| percent_decode_str(segment).decode_utf8_lossy().to_string())
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::{parse_responses, WebDavStore};
+
+    #[test]
+    fn parse_responses_ignores_case_of_non_ascii_bodies() {
+        // Lowercasing İ changes it's length, the positions must still point into the original body.
+        let body = "<D:multistatus><D:response><D:HREF>/gitcache/42/</D:HREF><D:displayname>İİİİ</D:displayname></D:response>\
+            <D:response><d:Href>/gitcache/42/Unix-main.zip</d:Href><D:displayname>ÄÖÜ</D:displayname></D:response></D:multistatus>";
+
+        let hrefs: Vec<Vec<String>> = parse_responses(body).into_iter().map(|(segments, _)| segments).collect();
+        assert_eq!(hrefs, vec![vec!["gitcache", "42"], vec!["gitcache", "42", "Unix-main.zip"]]);
+    }
+
+    #[test]
+    fn parse_responses_decodes_hrefs() {
+        let body = "<d:multistatus><d:response><d:href>/gitcache/42/Unix-feature%2Fa%20b%C3%BC.zip</d:href></d:response>\
+            <d:response><d:href>/gitcache/42/a&amp;b.zip</d:href></d:response></d:multistatus>";
+
+        let hrefs: Vec<Vec<String>> = parse_responses(body).into_iter().map(|(segments, _)| segments).collect();
+        assert_eq!(hrefs, vec![vec!["gitcache", "42", "Unix-feature/a bü.zip"], vec!["gitcache", "42", "a&b.zip"]]);
+    }
+
+    #[test]
+    fn parse_responses_reads_the_modification_time_of_each_response() {
+        let body = "<d:multistatus><d:response><d:href>/gitcache/42/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype>\
+            </d:prop></d:propstat></d:response><d:response><d:href>/gitcache/42/Unix-main.zip</d:href><d:propstat><d:prop>\
+            <d:getlastmodified>Sat, 17 Oct 2026 12:00:00 GMT</d:getlastmodified></d:prop></d:propstat></d:response></d:multistatus>";
+        let modified = SystemTime::from(chrono::DateTime::parse_from_rfc3339("2026-10-17T12:00:00Z").unwrap());
+
+        assert_eq!(parse_responses(body).into_iter().map(|(_, modified)| modified).collect::<Vec<Option<SystemTime>>>(), vec![None, Some(modified)]);
+    }
+
+    #[test]
+    fn url_encodes_every_segment() {
+        let store = WebDavStore {
+            client: rustydav::client::Client::init("user", "password"),
+            http_client: reqwest::blocking::Client::new(),
+            address: String::from("https://dav.example.com/remote.php/"),
+            user: String::from("user"),
+            password: String::from("password")
+        };
+
+        assert_eq!(store.url("/gitcache/42/a b#1%?ü.zip"), "https://dav.example.com/remote.php/gitcache/42/a%20b%231%25%3F%C3%BC.zip");
+    }
+}
@@ -0,0 +1,383 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use filetime::FileTime;
+use flate2::Compression as GzCompression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use crate::datas::enums::ArchiveFormat;
+use crate::datas::errors::CacheError;
+use crate::datas::structs::RestoreData;
+use crate::globs::ExcludeRules;
+use crate::{digest, helpers};
+
+/// Compression level used for tar.zst archives if no level was configured.
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Compression level used for tar.gz archives if no level was configured.
+const DEFAULT_GZIP_LEVEL: u32 = 6;
+
+/// [`tar_entries`] writes the data.json file and all cached objects as a tar archive into `writer`.
+/// The objects are read from their original location and stored under their name inside of the cache (see [`helpers::walk_restore_data`]).
+/// Symlinks are stored as links instead of being followed, file permissions and modification times are kept.
+///
+/// Arguments:
+///
+/// * `json_data`: type of [`&str`], the contents of the data.json file. It is the first entry of the archive.
+/// * `restore_data`: type of [`&[RestoreData]`], the objects that are archived.
+/// * `writer`: type of [`W`] that implements [`Write`]. Usually a compression encoder wrapping the destination file.
+/// * `excludes`: type of [`&ExcludeRules`], entries matching an exclude pattern are left out.
+///
+/// Returns:
+/// The writer after the archive was finished, so the caller can finish the compression stream.
+fn tar_entries<W: Write>(json_data: &str, restore_data: &[RestoreData], writer: W, excludes: &ExcludeRules) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json_data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default());
+    builder.append_data(&mut header, "data.json", json_data.as_bytes())?;
+
+    helpers::walk_restore_data(restore_data, excludes, &mut |path, name, metadata| {
+        // Folders are added with the metadata of their target, so a cached folder that is a symlink is stored as folder.
+        if metadata.is_dir() {
+            return builder.append_dir(name, path);
+        }
+
+        return builder.append_path_with_name(path, name);
+    })?;
+
+    return builder.into_inner();
+}
+
+/// [`tar_restore_data`] archives the data.json file and all cached objects into a compressed tar file.
+/// The objects are read straight from their original location, so no copy of them is needed.
+///
+/// Arguments:
+///
+/// * `json_data`: type of [`&str`], the contents of the data.json file describing `restore_data`.
+/// * `restore_data`: type of [`&[RestoreData]`], the objects that are archived.
+/// * `dst_file`: type of [`&str`], the path and name of the archive that is created.
+/// * `format`: type of [`ArchiveFormat`], either [`ArchiveFormat::TarZst`] or [`ArchiveFormat::TarGz`].
+/// * `level`: type of [`Option<i32>`], the compression level. [`None`] uses the default level of the format.
+/// * `excludes`: type of [`&ExcludeRules`], entries matching an exclude pattern are left out.
+/// * `header`: type of [`&[u8]`], written in front of the archive (see [`digest::create_header`]).
+///
+/// Returns:
+/// If the archiving of the objects completed successfuly. A partially written archive is removed if it failed.
+pub fn tar_restore_data(json_data: &str, restore_data: &[RestoreData], dst_file: &str, format: ArchiveFormat, level: Option<i32>, excludes: &ExcludeRules,
+    header: &[u8]) -> io::Result<()> {
+    let tar_result = write_tar_file(json_data, restore_data, dst_file, format, level, excludes, header);
+
+    if tar_result.is_err() {
+        let _ = fs::remove_file(dst_file);
+    }
+
+    return tar_result;
+}
+
+/// Creates the archive file of [`tar_restore_data`] and writes the compressed tar archive into it.
+fn write_tar_file(json_data: &str, restore_data: &[RestoreData], dst_file: &str, format: ArchiveFormat, level: Option<i32>, excludes: &ExcludeRules,
+    header: &[u8]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(dst_file)?);
+    file.write_all(header)?;
+
+    match format {
+        ArchiveFormat::TarZst => {
+            let mut encoder = zstd::Encoder::new(file, level.unwrap_or(DEFAULT_ZSTD_LEVEL))?;
+            // Lets the decoder detect corrupted archives, gzip always contains a checksum.
+            encoder.include_checksum(true)?;
+            tar_entries(json_data, restore_data, encoder, excludes)?.finish()?.flush()?;
+        }
+        ArchiveFormat::TarGz => {
+            let gz_level = level.map(|level| level as u32).unwrap_or(DEFAULT_GZIP_LEVEL);
+            let encoder = GzEncoder::new(file, GzCompression::new(gz_level));
+            tar_entries(json_data, restore_data, encoder, excludes)?.finish()?.flush()?;
+        }
+        ArchiveFormat::Zip => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Zip archives can not be created as tar archive."));
+        }
+    }
+
+    return Ok(());
+}
+
+/// [`open_archive`] opens a compressed tar archive for reading. Permissions and modification times are restored when unpacking entries.
+/// The header in front of the archive is skipped (see [`digest::skip_header`]).
+///
+/// Returns:
+/// The opened archive or an error if the file could not be opened.
+fn open_archive(fname: &Path, format: ArchiveFormat) -> Result<tar::Archive<Box<dyn Read>>, CacheError> {
+    let file = File::open(fname);
+
+    if file.is_err(){
+        return Err(CacheError::io("Could not open the archive to extract it.", file.unwrap_err()));
+    }
+
+    let mut file = file.unwrap();
+    let skip_result = digest::skip_header(&mut file);
+
+    if skip_result.is_err(){
+        return Err(CacheError::archive("Could not read the header of the archive.", skip_result.unwrap_err()));
+    }
+
+    let reader = BufReader::new(file);
+    let decoder: Box<dyn Read> = match format {
+        ArchiveFormat::TarZst => {
+            let zstd_decoder = zstd::Decoder::with_buffer(reader);
+
+            if zstd_decoder.is_err(){
+                return Err(CacheError::archive("Could not initialize the zstd decoder for the archive.", zstd_decoder.err().unwrap()));
+            }
+
+            Box::new(zstd_decoder.unwrap())
+        }
+        ArchiveFormat::TarGz => Box::new(GzDecoder::new(reader)),
+        ArchiveFormat::Zip => {
+            return Err(CacheError::invalid("Zip archives can not be extracted as tar archive."));
+        }
+    };
+
+    let mut archive = tar::Archive::new(decoder);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    return Ok(archive);
+}
+
+/// [`read_data_file`] reads the data.json file of a compressed tar archive without extracting the archive.
+/// The data.json file is the first entry of archives created by this tool, so usually only the start of the archive is read.
+///
+/// Arguments:
+///
+/// * `fname`: type of [`&Path`], the archive.
+/// * `format`: type of [`ArchiveFormat`], the compression of the archive.
+///
+/// Returns:
+/// The contents of the data.json file or an error if the archive does not contain one.
+pub fn read_data_file(fname: &Path, format: ArchiveFormat) -> Result<String, CacheError> {
+    let archive = open_archive(fname, format);
+
+    if archive.is_err(){
+        return Err(archive.err().unwrap());
+    }
+
+    let mut archive = archive.unwrap();
+    let entries = archive.entries();
+
+    if entries.is_err(){
+        return Err(CacheError::archive("Could not read the entries of the tar archive. The archive may be corrupted.", entries.err().unwrap()));
+    }
+
+    for entry in entries.unwrap(){
+        if entry.is_err(){
+            return Err(CacheError::archive("Encountered an error while reading the tar archive. The archive may be corrupted.", entry.err().unwrap()));
+        }
+
+        let mut entry = entry.unwrap();
+
+        if !entry.path().is_ok_and(|path| path == Path::new("data.json")) {
+            continue;
+        }
+
+        let mut data = String::new();
+        let read_result = entry.read_to_string(&mut data);
+
+        if read_result.is_err(){
+            return Err(CacheError::archive("Could not read the data.json file of the tar archive. The archive may be corrupted.", read_result.unwrap_err()));
+        }
+
+        return Ok(data);
+    }
+
+    return Err(CacheError::invalid("The archive does not contain a data.json file. This is needed to restore the items to the locations they're supposed to be."));
+}
+
+/// [`untar_file`] extracts a compressed tar archive to the paths returned by `target`.
+/// Symlinks, permissions and modification times of the entries are restored.
+///
+/// Arguments:
+///
+/// * `fname`: type of [`&Path`], the path of the archive to extract.
+/// * `format`: type of [`ArchiveFormat`], the compression of the archive.
+/// * `target`: type of [`&helpers::ExtractTarget`], where the entries of the archive are extracted to.
+///
+/// Returns:
+/// An error if the archive could not be read or an entry could not be written.
+/// Everything that was extracted before the error is removed again, so no partial cache is left behind.
+pub fn untar_file(fname: &Path, format: ArchiveFormat, target: &helpers::ExtractTarget) -> Result<(), CacheError> {
+    let archive = open_archive(fname, format);
+
+    if archive.is_err(){
+        return Err(archive.err().unwrap());
+    }
+
+    let mut archive = archive.unwrap();
+    let mut created_paths: Vec<PathBuf> = Vec::new();
+    let mut extract_result = extract_entries(&mut archive, target, &mut created_paths);
+
+    // The tar reader stops at the end of the archive, reading the rest of the stream makes the decoder verify the checksum
+    // stored at the end of the compressed data.
+    if extract_result.is_ok(){
+        let drain_result = io::copy(&mut archive.into_inner(), &mut io::sink());
+
+        if drain_result.is_err(){
+            extract_result = Err(CacheError::archive("The checksum of the compressed tar archive does not match. The archive is corrupted.", drain_result.unwrap_err()));
+        }
+    }
+
+    if extract_result.is_err(){
+        helpers::remove_extracted(&created_paths);
+    }
+
+    return extract_result;
+}
+
+/// [`extract_entries`] extracts all entries of a tar archive to the paths returned by `target`.
+/// Only files, directories and symlinks are extracted, other entry types (e.g. hard links or devices) are skipped.
+///
+/// Arguments:
+///
+/// * `archive`: type of [`&mut tar::Archive<Box<dyn Read>>`], the opened archive.
+/// * `target`: type of [`&helpers::ExtractTarget`], where the entries are extracted to.
+/// * `created_paths`: type of [`&mut Vec<PathBuf>`], every file or folder created by the extraction is added to it,
+/// so the caller can remove them if the extraction fails.
+///
+/// Returns:
+/// An error if an entry could not be read or written.
+fn extract_entries(archive: &mut tar::Archive<Box<dyn Read>>, target: &helpers::ExtractTarget, created_paths: &mut Vec<PathBuf>) -> Result<(), CacheError> {
+    let entries = archive.entries();
+
+    if entries.is_err(){
+        return Err(CacheError::archive("Could not read the entries of the tar archive. The archive may be corrupted.", entries.err().unwrap()));
+    }
+
+    // Directories are finished after all entries were extracted, since extracting into them changes their modification time
+    // and read only directories would prevent extracting their contents.
+    let mut extracted_dirs: Vec<(PathBuf, Option<u32>, Option<FileTime>)> = Vec::new();
+
+    for entry in entries.unwrap(){
+        if entry.is_err(){
+            return Err(CacheError::archive("Encountered an error while reading the tar archive. The archive may be corrupted.", entry.err().unwrap()));
+        }
+
+        let mut entry = entry.unwrap();
+        let entry_name = entry.path().map(|path| path.to_path_buf()).unwrap_or_default();
+        let outpath = match target.resolve(&entry_name) {
+            Some(path) if !entry_name.as_os_str().is_empty() => path,
+            _ => continue,
+        };
+
+        let entry_type = entry.header().entry_type();
+
+        if !(entry_type.is_dir() || entry_type.is_file() || entry_type.is_symlink()) {
+            eprintln!("Skipping archive entry {} since it's type is not supported.", entry_name.display());
+            continue;
+        }
+
+        let prepare_result = target.prepare(&outpath, entry_type.is_dir(), created_paths);
+
+        if prepare_result.is_err(){
+            return Err(prepare_result.unwrap_err());
+        }
+
+        if !prepare_result.unwrap() {
+            eprintln!("Skipping archive entry {} since it would be extracted outside of the allowed directories.", entry_name.display());
+            continue;
+        }
+
+        if entry_type.is_dir() {
+            let create_result = fs::create_dir_all(&outpath);
+
+            if create_result.is_err(){
+                return Err(CacheError::io("Could not create a directory while extracting the tar archive.", create_result.unwrap_err()));
+            }
+
+            let mode = entry.header().mode().ok();
+            let modified = entry.header().mtime().ok().map(|mtime| FileTime::from_unix_time(mtime as i64, 0));
+            extracted_dirs.push((outpath, mode, modified));
+            continue;
+        }
+
+        if entry_type.is_symlink() && !entry.link_name().ok().flatten().is_some_and(|link_target| target.allows_link(&outpath, &link_target)) {
+            eprintln!("Skipping archive entry {} since it is a symlink pointing outside of the allowed directories.", entry_name.display());
+            continue;
+        }
+
+        let unpack_result = entry.unpack(&outpath);
+
+        if unpack_result.is_err(){
+            return Err(CacheError::archive("Encountered an error while extracting the tar archive. The archive may be corrupted.", unpack_result.unwrap_err()));
+        }
+    }
+
+    for (outpath, mode, modified) in extracted_dirs.iter().rev() {
+        helpers::set_extracted_metadata(outpath, *mode, *modified);
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::datas::{enums::ArchiveFormat, errors::CacheError};
+    use crate::testutils::{self, Entry, TestEnv};
+
+    /// The tar formats, zip archives are tested in [`crate::zip`].
+    const TAR_FORMATS: [ArchiveFormat; 2] = [ArchiveFormat::TarZst, ArchiveFormat::TarGz];
+
+    #[test]
+    fn truncated_archives_remove_the_partial_output() {
+        for format in TAR_FORMATS {
+            let test_env = TestEnv::new();
+            let archive = test_env.path().join(format!("cache{}", format.extension()));
+            let into = test_env.path().join("extracted");
+            let contents = testutils::random_text(256 * 1024);
+            testutils::write_tar(&archive, format, &[Entry::File("deps/a.txt", "a"), Entry::File("deps/b.txt", &contents)]);
+            let archive_contents = fs::read(&archive).unwrap();
+            fs::write(&archive, &archive_contents[..archive_contents.len() * 3 / 4]).unwrap();
+
+            let extract_result = testutils::extract_archive(&archive, format, &into, &test_env.path());
+            assert!(matches!(extract_result, Err(CacheError::Archive { .. })), "{:?}: {:?}", format, extract_result.err());
+            assert_eq!(extract_result.unwrap_err().exit_code(), 6);
+            assert!(!into.exists(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn corrupted_archives_remove_the_partial_output() {
+        for format in TAR_FORMATS {
+            let test_env = TestEnv::new();
+            let archive = test_env.path().join(format!("cache{}", format.extension()));
+            let into = test_env.path().join("extracted");
+            let contents = testutils::random_text(256 * 1024);
+            testutils::write_tar(&archive, format, &[Entry::File("deps/a.txt", "a"), Entry::File("deps/b.txt", &contents)]);
+            let mut archive_contents = fs::read(&archive).unwrap();
+            let middle = archive_contents.len() / 2;
+            archive_contents[middle] ^= 0xff;
+            fs::write(&archive, archive_contents).unwrap();
+
+            let extract_result = testutils::extract_archive(&archive, format, &into, &test_env.path());
+            assert!(matches!(extract_result, Err(CacheError::Archive { .. })), "{:?}: {:?}", format, extract_result.err());
+            assert!(!into.exists(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn files_that_are_no_archives_are_archive_errors() {
+        for format in TAR_FORMATS {
+            let test_env = TestEnv::new();
+            let archive = test_env.write("cache.tar", "this is not an archive");
+            let into = test_env.path().join("extracted");
+
+            let extract_result = testutils::extract_archive(&archive, format, &into, &test_env.path());
+            assert!(matches!(extract_result, Err(CacheError::Archive { .. })), "{:?}: {:?}", format, extract_result.err());
+            assert!(matches!(super::read_data_file(&archive, format), Err(CacheError::Archive { .. })), "{:?}", format);
+            assert!(!into.exists(), "{:?}", format);
+        }
+    }
+}
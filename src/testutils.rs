@@ -0,0 +1,258 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::{env, fs};
+
+use tempfile::TempDir;
+
+use crate::envfuncs;
+
+/// The commands read their configuration from enviorement variables and work in the current directory,
+/// which are shared by all tests of the process. Every test using them holds this lock.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Prefixes of the enviorement variables that are cleared before every test, so the enviorement of the machine running
+/// the tests does not leak into them.
+const CLEARED_PREFIXES: [&str; 8] = ["cachepath_", "cachefile_", "CACHE_", "CI_", "S3", "FSROOT", "FTP", "WEBDAV"];
+
+/// [`TestEnv`] runs a test inside of an empty temporary directory with a clean set of enviorement variables.
+/// `CI_PROJECT_ID`, `CI_COMMIT_BRANCH` and `CI_PROJECT_DIR` (the temporary directory) are set by default.
+/// Everything is reset once the [`TestEnv`] is dropped.
+pub struct TestEnv {
+    dir: TempDir,
+    old_dir: PathBuf,
+    old_vars: Vec<(OsString, OsString)>,
+    _lock: MutexGuard<'static, ()>
+}
+
+impl TestEnv {
+    /// Creates the temporary directory and makes it the current directory.
+    pub fn new() -> TestEnv {
+        let lock = ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let old_vars: Vec<(OsString, OsString)> = env::vars_os()
+            .filter(|(name, _)| CLEARED_PREFIXES.iter().any(|prefix| name.to_string_lossy().starts_with(prefix)))
+            .collect();
+
+        for (name, _) in &old_vars {
+            env::remove_var(name);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let old_dir = env::current_dir().unwrap();
+        env::set_current_dir(dir.path()).unwrap();
+
+        let test_env = TestEnv { dir, old_dir, old_vars, _lock: lock };
+        test_env.set("CI_PROJECT_ID", "42");
+        test_env.set("CI_COMMIT_BRANCH", "main");
+        test_env.set("CI_PROJECT_DIR", test_env.path().to_str().unwrap());
+        return test_env;
+    }
+
+    /// The canonical path of the temporary directory.
+    pub fn path(&self) -> PathBuf {
+        return fs::canonicalize(self.dir.path()).unwrap();
+    }
+
+    /// Sets the enviorement variable `name` for the rest of the test.
+    pub fn set(&self, name: &str, value: &str) {
+        env::set_var(name, value);
+    }
+
+    /// Writes `contents` to the file at `path` relative to the temporary directory, creating it's parent directories.
+    pub fn write(&self, path: &str, contents: &str) -> PathBuf {
+        let file_path = self.path().join(path);
+        fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        fs::write(&file_path, contents).unwrap();
+        return file_path;
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        // The work directory is named after the project directory of the test, so it is never shared with another test.
+        if let Ok(work_dir) = envfuncs::get_work_dir() {
+            let _ = fs::remove_dir_all(work_dir);
+        }
+
+        let _ = env::set_current_dir(&self.old_dir);
+        let test_vars: Vec<OsString> = env::vars_os()
+            .map(|(name, _)| name)
+            .filter(|name| CLEARED_PREFIXES.iter().any(|prefix| name.to_string_lossy().starts_with(prefix)))
+            .collect();
+
+        for name in test_vars {
+            env::remove_var(name);
+        }
+
+        for (name, value) in &self.old_vars {
+            env::set_var(name, value);
+        }
+    }
+}
+
+/// Reads the file at `path`, panics if it does not exist.
+pub fn read(path: &Path) -> String {
+    return fs::read_to_string(path).unwrap_or_else(|_| panic!("Expected {} to exist.", path.display()));
+}
+
+/// [`TrackingAllocator`] counts the bytes allocated by each thread, so tests can check how much memory an operation needs
+/// (see [`peak_allocation`]). Allocations of other threads, e.g. other tests running in parallel, are not counted.
+struct TrackingAllocator;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Adds `size` bytes to the allocations of the current thread. Does nothing while the thread is being torn down.
+fn track(size: isize) {
+    let _ = ALLOCATED.try_with(|allocated| {
+        allocated.set(allocated.get() + size);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(allocated.get())));
+    });
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        track(layout.size() as isize);
+        return System.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        track(-(layout.size() as isize));
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        track(new_size as isize - layout.size() as isize);
+        return System.realloc(ptr, layout, new_size);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+/// [`peak_allocation`] runs `operation` and measures the most memory the current thread had allocated at once while it ran.
+///
+/// Returns:
+/// The result of `operation` and the peak in bytes, relative to the memory allocated before it started.
+pub fn peak_allocation<T>(operation: impl FnOnce() -> T) -> (T, usize) {
+    let start = ALLOCATED.with(|allocated| allocated.get());
+    PEAK.with(|peak| peak.set(start));
+    let result = operation();
+    let peak = PEAK.with(|peak| peak.get());
+
+    return (result, (peak - start).max(0) as usize);
+}
+
+/// An entry of a fixture archive written by [`write_zip`] or [`write_tar`]. Names and link targets are stored as they are,
+/// so fixtures can contain entries a well behaved archiver would never write (e.g. `../evil.txt`).
+pub enum Entry<'a> {
+    /// A file with it's name and contents.
+    File(&'a str, &'a str),
+    /// A symlink with it's name and target.
+    Link(&'a str, &'a str)
+}
+
+/// Writes a zip archive containing `entries` to `path`.
+pub fn write_zip(path: &Path, entries: &[Entry]) {
+    use std::io::Write;
+
+    let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+    let options = zip::write::FileOptions::default();
+
+    for entry in entries {
+        match entry {
+            Entry::File(name, contents) => {
+                zip.start_file(*name, options.unix_permissions(0o644)).unwrap();
+                zip.write_all(contents.as_bytes()).unwrap();
+            }
+            Entry::Link(name, link_target) => zip.add_symlink(*name, *link_target, options).unwrap()
+        }
+    }
+
+    zip.finish().unwrap();
+}
+
+/// Writes a tar archive containing `entries` to `path`, compressed like archives of the given `format`.
+pub fn write_tar(path: &Path, format: crate::datas::enums::ArchiveFormat, entries: &[Entry]) {
+    use crate::datas::enums::ArchiveFormat;
+
+    let mut tar_data = tar::Builder::new(Vec::new());
+
+    for entry in entries {
+        let (name, link_target, contents) = match entry {
+            Entry::File(name, contents) => (*name, "", *contents),
+            Entry::Link(name, link_target) => (*name, *link_target, "")
+        };
+
+        // The names are copied into the header directly, since the setters of the header refuse `..` and absolute paths.
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.as_old_mut().linkname[..link_target.len()].copy_from_slice(link_target.as_bytes());
+        header.set_entry_type(if link_target.is_empty() { tar::EntryType::Regular } else { tar::EntryType::Symlink });
+        header.set_mode(0o644);
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        tar_data.append(&header, contents.as_bytes()).unwrap();
+    }
+
+    let tar_data = tar_data.into_inner().unwrap();
+    let compressed = match format {
+        ArchiveFormat::TarZst => {
+            use std::io::Write;
+
+            // Archives created by this tool contain a checksum, so the decoder detects corrupted data.
+            let mut encoder = zstd::Encoder::new(Vec::new(), 0).unwrap();
+            encoder.include_checksum(true).unwrap();
+            encoder.write_all(&tar_data).unwrap();
+            encoder.finish().unwrap()
+        }
+        _ => {
+            use std::io::Write;
+
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&tar_data).unwrap();
+            encoder.finish().unwrap()
+        }
+    };
+
+    fs::write(path, compressed).unwrap();
+}
+
+/// Writes a fixture archive in `format` containing `entries` to `path` (see [`write_zip`] and [`write_tar`]).
+pub fn write_archive(path: &Path, format: crate::datas::enums::ArchiveFormat, entries: &[Entry]) {
+    match format {
+        crate::datas::enums::ArchiveFormat::Zip => write_zip(path, entries),
+        _ => write_tar(path, format, entries)
+    }
+}
+
+/// Extracts the archive at `path` into the directory `into`, like the commands do.
+/// Only `root` is allowed as destination of the entries and the targets of symlinks.
+pub fn extract_archive(path: &Path, format: crate::datas::enums::ArchiveFormat, into: &Path, root: &Path) -> Result<(), crate::datas::errors::CacheError> {
+    use crate::datas::enums::ArchiveFormat;
+
+    let destination = |name: &Path| -> Option<PathBuf> { return Some(into.join(name)); };
+    let roots = [fs::canonicalize(root).unwrap()];
+    let target = crate::helpers::ExtractTarget { destination: &destination, roots: &roots, check_links: true };
+
+    return match format {
+        ArchiveFormat::Zip => crate::zip::unzip_file(path, &target),
+        _ => crate::tar::untar_file(path, format, &target)
+    };
+}
+
+/// Returns `length` bytes of pseudo random hex digits, which don't compress well.
+pub fn random_text(length: usize) -> String {
+    let mut state: u64 = 0x2545f4914f6cdd1d;
+
+    return (0..length).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        return char::from_digit((state % 16) as u32, 16).unwrap();
+    }).collect();
+}
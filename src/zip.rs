@@ -1,102 +1,225 @@
-use std::io::{prelude::*, self};
-use std::io::{Seek, Write};
+use std::io;
+use std::io::{Read, Seek, Write};
 use std::iter::Iterator;
-use std::ops::Deref;
-use zip::result::ZipError;
+use chrono::{Datelike, Timelike, Utc};
+use filetime::FileTime;
 use zip::write::FileOptions;
 use std::fs::{File, self};
-use std::path::Path;
-use walkdir::{DirEntry, WalkDir};
+use std::path::{Path, PathBuf};
+use crate::datas::errors::CacheError;
+use crate::datas::structs::{Compression, RestoreData};
+use crate::globs::ExcludeRules;
+use crate::helpers;
 
-/// [`zip_dir`] takes an iterator of directory entries, a prefix, a writer, and a compression
-/// method, and creates a zip archive by adding files and directories from the iterator to the writer.
+/// File type bits of a symlink in the unix mode stored in zip entries.
+const UNIX_SYMLINK_MODE: u32 = 0o120000;
+
+/// Mask of the file type bits of a unix mode.
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+
+/// [`zip_entries`] creates a zip archive containing the data.json file and all cached objects and writes it to `writer`.
+/// The objects are read from their original location and stored under their name inside of the cache (see [`helpers::walk_restore_data`]).
+/// Symlinks are stored as symlinks and every entry keeps it's unix permissions and modification time.
+/// Zip archives store modification times with a precision of two seconds.
 /// 
 /// Arguments:
 /// 
-/// * `it`: type of [`&mut dyn Iterator<Item = DirEntry>`], an iterator representing the items to put into the zip. 
-/// * `prefix`: type of [`String`], that represents the common prefix that should be
-/// stripped from the file paths before adding them to the zip archive. This is useful when you want to
-/// create a zip archive that contains files from a specific directory, but you don't want the directory
-/// structure to be included in
+/// * `json_data`: type of [`&str`], the contents of the data.json file. It is the first entry of the archive.
+/// * `restore_data`: type of [`&[RestoreData]`], the objects that are archived.
 /// * `writer`: type of [`T`] that implements the [`Write`] and [`Seek`] traits. 
 /// It represents the output stream where the zip file will be written to. It could be any type
 /// that implements these traits, such as a [`File`] or a [`TcpStream`].
 /// * `method`: type of [`zip::CompressionMethod`] and is used to specify the
 /// compression method to be used when creating the zip file. The [`zip::CompressionMethod`] enum provides
 /// different compression methods such as `Stored`, `Deflated`, `Bzip2`, etc.
+/// * `level`: type of [`Option<i32>`], the compression level. [`None`] uses the default level of the compression method.
+/// * `comment`: type of [`&str`], the comment stored in the zip archive.
+/// * `excludes`: type of [`&ExcludeRules`], entries matching an exclude pattern are left out.
 /// 
 /// Returns:
-/// If the zipping of the directory completed successfuly.
-fn zip_dir<T>(
-    it: &mut dyn Iterator<Item = DirEntry>,
-    prefix: &str,
+/// If the zipping of the objects completed successfuly.
+fn zip_entries<T>(
+    json_data: &str,
+    restore_data: &[RestoreData],
     writer: T,
-    method: zip::CompressionMethod
+    method: zip::CompressionMethod,
+    level: Option<i32>,
+    comment: &str,
+    excludes: &ExcludeRules
 ) -> zip::result::ZipResult<()> where T: Write + Seek,
 {
     let mut zip = zip::ZipWriter::new(writer);
-    let options = FileOptions::default()
+    let default_options = FileOptions::default()
         .compression_method(method)
-        .unix_permissions(0o755);
+        .compression_level(level);
+
+    zip.set_comment(comment);
+    zip.start_file("data.json", default_options.unix_permissions(0o644))?;
+    zip.write_all(json_data.as_bytes())?;
 
-    let mut buffer = Vec::new();
-    for entry in it {
-        let path = entry.path();
-        let name = path.strip_prefix(Path::new(prefix)).unwrap();
+    helpers::walk_restore_data(restore_data, excludes, &mut |path, name, metadata| {
+        let options = default_options
+            .unix_permissions(get_unix_mode(metadata))
+            .last_modified_time(to_zip_time(metadata));
+        let entry_name = name.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
 
         // Write file or directory explicitly
         // Some unzip tools unzip files with directory paths correctly, some do not!
-        if path.is_file() {
-            #[allow(deprecated)]
-            zip.start_file_from_path(name, options)?;
+        if metadata.file_type().is_symlink() {
+            let link_target = fs::read_link(path)?;
+            zip.add_symlink(entry_name, link_target.to_string_lossy(), options)?;
+        } else if metadata.is_file() {
+            zip.start_file(entry_name, options)?;
             let mut f = File::open(path)?;
 
-            f.read_to_end(&mut buffer)?;
-            zip.write_all(&buffer)?;
-            buffer.clear();
-        } else if !name.as_os_str().is_empty() {
-            // Only if not root! Avoids path spec / warning
-            // and mapname conversion failed error on unzip
-            #[allow(deprecated)]
-            zip.add_directory_from_path(name, options)?;
+            // Stream the file into the archive so large files don't have to fit into memory.
+            io::copy(&mut f, &mut zip)?;
+        } else if metadata.is_dir() {
+            zip.add_directory(entry_name, options)?;
         }
-    }
+
+        return Ok(());
+    })?;
+
     zip.finish()?;
     Result::Ok(())
 }
 
 
 
-/// [`zip_dir_recursively`] recursively zips a directory and its contents into a destination
-/// file using the specified compression method.
+/// [`get_unix_mode`] returns the unix permissions of a file system entry.
+/// On windows only the readonly flag is known, so the default permissions of zip entries are used.
+fn get_unix_mode(metadata: &fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        return metadata.permissions().mode();
+    }
+
+    #[cfg(windows)]
+    {
+        let mode = if metadata.is_dir() { 0o755 } else { 0o644 };
+
+        if metadata.permissions().readonly() {
+            return mode & 0o555;
+        }
+
+        return mode;
+    }
+}
+
+/// [`to_zip_time`] converts the modification time of a file system entry into the format stored in zip archives.
+/// The time is stored as UTC, times the zip format can't represent (before 1980) fall back to the zip default.
+fn to_zip_time(metadata: &fs::Metadata) -> zip::DateTime {
+    let modified = metadata.modified();
+
+    if modified.is_err(){
+        return zip::DateTime::default();
+    }
+
+    let modified: chrono::DateTime<Utc> = modified.unwrap().into();
+
+    return zip::DateTime::from_date_and_time(
+        modified.year() as u16,
+        modified.month() as u8,
+        modified.day() as u8,
+        modified.hour() as u8,
+        modified.minute() as u8,
+        modified.second() as u8
+    ).unwrap_or_default();
+}
+
+/// [`from_zip_time`] converts the modification time of a zip entry back into a [`FileTime`], see [`to_zip_time`].
+fn from_zip_time(time: zip::DateTime) -> Option<FileTime> {
+    let date = chrono::NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?;
+    let date_time = date.and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)?;
+
+    return Some(FileTime::from_unix_time(date_time.and_utc().timestamp(), 0));
+}
+
+/// [`zip_restore_data`] zips the data.json file and all cached objects into a destination file using the specified compression method.
+/// The objects are read straight from their original location, so no copy of them is needed.
 /// 
 /// Arguments:
 /// 
-/// * `src_dir`: type of [`&str`], that represents the source directory from which you
-/// want to recursively zip all files and subdirectories.
-/// * `dst_file`: type of [`&str`], representing the path and name of the destination file where the zipped
-/// directory will be created.
-/// * `method`: type of function [`zip::CompressionMethod`]. It is used to specify the compression method to be used when creating the
-/// zip file. The [`zip::CompressionMethod`] enum provides different compression methods such as `Stored`, `Deflated, `Bzip2`, etc.
+/// * `json_data`: type of [`&str`], the contents of the data.json file describing `restore_data`.
+/// * `restore_data`: type of [`&[RestoreData]`], the objects that are archived.
+/// * `dst_file`: type of [`&str`], representing the path and name of the destination file where the zip archive
+/// will be created.
+/// * `compression`: type of [`Compression`], the compression algorithm and level. It is recorded in the comment of the archive.
+/// * `excludes`: type of [`&ExcludeRules`], entries matching an exclude pattern are left out.
+/// * `header`: type of [`&[u8]`], written in front of the archive. Zip readers find their central directory despite it.
 /// 
 /// Returns:
-/// If the zipping of the directory completed successfuly.
-pub fn zip_dir_recursively(src_dir: &str,dst_file: &str,method: zip::CompressionMethod,) 
+/// If the zipping of the objects completed successfuly. A partially written archive is removed if it failed.
+pub fn zip_restore_data(json_data: &str, restore_data: &[RestoreData], dst_file: &str, compression: Compression, excludes: &ExcludeRules, header: &[u8])
     -> zip::result::ZipResult<()> {
 
-    if !Path::new(src_dir).is_dir() {
-        return Err(ZipError::FileNotFound);
+    let comment = format!("worker-cache compression={}", compression.to_string());
+    let path = Path::new(dst_file);
+    let mut file = File::create(path)?;
+    // The offsets stored in the archive are counted from the start of the file, so they include the header.
+    let zip_result = file.write_all(header)
+        .map_err(zip::result::ZipError::from)
+        .and_then(|_| zip_entries(json_data, restore_data, file, compression.compression_type.to_zip_method(), compression.level, comment.as_str(), excludes));
+
+    if zip_result.is_err(){
+        let _ = fs::remove_file(path);
     }
 
-    let path = Path::new(dst_file);
-    let file = File::create(path).unwrap();
+    return zip_result;
+}
+
+/// [`open_archive`] opens a zip archive for reading.
+///
+/// Returns:
+/// The opened archive or an error if the file could not be opened or is not a valid zip archive.
+fn open_archive(fname: &Path) -> Result<zip::ZipArchive<File>, CacheError> {
+    let file = fs::File::open(fname);
+
+    if file.is_err(){
+        return Err(CacheError::io("Could not open the archive to extract it.", file.unwrap_err()));
+    }
+
+    let archive = zip::ZipArchive::new(file.unwrap());
+
+    if archive.is_err(){
+        return Err(CacheError::zip("Could not read the zip archive. The archive may be corrupted.", archive.unwrap_err()));
+    }
 
-    let walkdir = WalkDir::new(src_dir);
-    let it = walkdir.into_iter();
+    return Ok(archive.unwrap());
+}
+
+/// [`read_data_file`] reads the data.json file of a zip archive without extracting the archive.
+///
+/// Arguments:
+///
+/// * `fname`: type of [`&Path`], the zip archive.
+///
+/// Returns:
+/// The contents of the data.json file or an error if the archive does not contain one.
+pub fn read_data_file(fname: &Path) -> Result<String, CacheError> {
+    let archive = open_archive(fname);
+
+    if archive.is_err(){
+        return Err(archive.unwrap_err());
+    }
 
-    zip_dir(&mut it.filter_map(|e| e.ok()), src_dir, file, method)?;
+    let mut archive = archive.unwrap();
+    let data_file = archive.by_name("data.json");
+
+    if data_file.is_err(){
+        return Err(CacheError::zip("The archive does not contain a data.json file. This is needed to restore the items to the locations they're supposed to be.", data_file.err().unwrap()));
+    }
+
+    let mut data = String::new();
+    let read_result = data_file.unwrap().read_to_string(&mut data);
+
+    if read_result.is_err(){
+        return Err(CacheError::archive("Could not read the data.json file of the zip archive. The archive may be corrupted.", read_result.unwrap_err()));
+    }
 
-    Ok(())
+    return Ok(data);
 }
 
 /// [`unzip_file`] takes a file name as input, attempts to unzip the file, and returns a
@@ -104,66 +227,197 @@ pub fn zip_dir_recursively(src_dir: &str,dst_file: &str,method: zip::Compression
 /// 
 /// Arguments:
 /// 
-/// * `fname`: type of [`String`] that represents the file name or path of the zip file
+/// * `fname`: type of [`&Path`] that represents the file name or path of the zip file
 /// that you want to unzip.
+/// * `target`: type of [`&helpers::ExtractTarget`], where the entries of the archive are extracted to.
 /// 
 /// Returns:
-/// Returns an error message, containing the error that was hit during the unzip process
-pub fn unzip_file(fname: String) -> Result<(), &'static str> {
+/// Returns an error, containing the error that was hit during the unzip process.
+/// Everything that was extracted before the error is removed again, so no partial cache is left behind.
+pub fn unzip_file(fname: &Path, target: &helpers::ExtractTarget) -> Result<(), CacheError> {
+    let archive = open_archive(fname);
 
-    let file_name_cannonicalize_result = fs::canonicalize(fname.clone());
-
-    if file_name_cannonicalize_result.is_err(){
-        return Result::Err("Could not canonicalize file please make sure the path is correct. 
-        For more information about canonicalization visit: https://doc.rust-lang.org/std/fs/fn.canonicalize.html");
+    if archive.is_err(){
+        return Err(archive.unwrap_err());
     }
 
-    let file_pathbuf = file_name_cannonicalize_result.ok().unwrap();
+    let mut archive = archive.unwrap();
 
-    if !fs::metadata(file_pathbuf.clone()).is_ok(){
-        return Result::Err("Could not find directory at zip location");
+    // The compression method is stored per entry, so archives of any supported method are extracted the same way.
+    let comment = String::from_utf8_lossy(archive.comment()).to_string();
+    if !comment.is_empty(){
+        println!("Extracting archive: {}", comment);
     }
 
-    let parent_path = file_pathbuf.deref().parent();
+    let mut created_paths: Vec<PathBuf> = Vec::new();
+    let extract_result = extract_entries(&mut archive, target, &mut created_paths);
 
-    if parent_path.is_none(){
-        return Result::Err("Could not find parentpath, parentpath returned nothing");
+    if extract_result.is_err(){
+        helpers::remove_extracted(&created_paths);
     }
 
-    let parent_path = parent_path.unwrap();
+    return extract_result;
+}
 
-    let file = fs::File::open(fname).unwrap();
-    let mut archive = zip::ZipArchive::new(file).unwrap();
+/// [`extract_entries`] extracts all entries of a zip archive to the paths returned by `target`.
+///
+/// Arguments:
+///
+/// * `archive`: type of [`&mut zip::ZipArchive<File>`], the opened archive.
+/// * `target`: type of [`&helpers::ExtractTarget`], where the entries are extracted to.
+/// * `created_paths`: type of [`&mut Vec<PathBuf>`], every file or folder created by the extraction is added to it,
+/// so the caller can remove them if the extraction fails.
+///
+/// Returns:
+/// An error if an entry could not be read or written.
+fn extract_entries(archive: &mut zip::ZipArchive<File>, target: &helpers::ExtractTarget, created_paths: &mut Vec<PathBuf>) -> Result<(), CacheError> {
+    // Directories are finished after all entries were extracted, since extracting into them changes their modification time.
+    let mut extracted_dirs: Vec<(PathBuf, Option<u32>, Option<FileTime>)> = Vec::new();
 
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-        let outpath = match file.enclosed_name() {
-            Some(path) => parent_path.join(path.to_owned()),
+        let file = archive.by_index(i);
+
+        if file.is_err(){
+            return Err(CacheError::zip("Could not read an entry of the zip archive. The archive may be corrupted or truncated.", file.err().unwrap()));
+        }
+
+        let mut file = file.unwrap();
+        let outpath = match file.enclosed_name().and_then(|name| target.resolve(name)) {
+            Some(path) => path,
             None => continue,
         };
-        
-        if (*file.name()).ends_with('/') {
-            fs::create_dir_all(&outpath).unwrap();
-        } else {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(p).unwrap();
-                }
+
+        let mode = file.unix_mode();
+        let modified = from_zip_time(file.last_modified());
+        let is_dir = (*file.name()).ends_with('/');
+        let prepare_result = target.prepare(&outpath, is_dir, created_paths);
+
+        if prepare_result.is_err(){
+            return Err(prepare_result.unwrap_err());
+        }
+
+        if !prepare_result.unwrap() {
+            eprintln!("Skipping archive entry {} since it would be extracted outside of the allowed directories.", file.name());
+            continue;
+        }
+
+        if is_dir {
+            let create_result = fs::create_dir_all(&outpath);
+
+            if create_result.is_err(){
+                return Err(CacheError::io("Could not create a directory while extracting the zip archive.", create_result.unwrap_err()));
             }
-            let mut outfile = fs::File::create(&outpath).unwrap();
-            io::copy(&mut file, &mut outfile).unwrap();
+
+            extracted_dirs.push((outpath, mode, modified));
+            continue;
         }
 
-        // Get and Set permissions
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
+        if mode.is_some_and(|mode| mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK_MODE) {
+            let mut link_target = String::new();
+            let read_result = file.read_to_string(&mut link_target);
 
-            if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode)).unwrap();
+            if read_result.is_err(){
+                return Err(CacheError::archive("Could not read a symlink stored in the archive.", read_result.unwrap_err()));
             }
+
+            if !target.allows_link(&outpath, Path::new(&link_target)) {
+                eprintln!("Skipping archive entry {} since it is a symlink pointing outside of the allowed directories.", file.name());
+                continue;
+            }
+
+            let symlink_result = helpers::create_symlink(Path::new(&link_target), &outpath);
+
+            if symlink_result.is_err(){
+                return Err(CacheError::io("Could not create a symlink stored in the archive.", symlink_result.unwrap_err()));
+            }
+
+            continue;
         }
+
+        let outfile = fs::File::create(&outpath);
+
+        if outfile.is_err(){
+            return Err(CacheError::io("Could not create a file while extracting the zip archive.", outfile.unwrap_err()));
+        }
+
+        let mut outfile = outfile.unwrap();
+        let copy_result = io::copy(&mut file, &mut outfile);
+        drop(outfile);
+
+        if copy_result.is_err(){
+            return Err(CacheError::archive("Could not extract a file of the zip archive. The archive may be corrupted or the disk is full.", copy_result.unwrap_err()));
+        }
+
+        helpers::set_extracted_metadata(&outpath, mode, modified);
+    }
+
+    for (outpath, mode, modified) in extracted_dirs.iter().rev() {
+        helpers::set_extracted_metadata(outpath, *mode, *modified);
     }
 
     return Ok(());
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::datas::{enums::ArchiveFormat, errors::CacheError};
+    use crate::testutils::{self, Entry, TestEnv};
+
+    #[test]
+    fn truncated_archives_are_zip_errors() {
+        let test_env = TestEnv::new();
+        let archive = test_env.path().join("cache.zip");
+        let into = test_env.path().join("extracted");
+        testutils::write_zip(&archive, &[Entry::File("data.json", "[]"), Entry::File("deps/a.txt", "a")]);
+        let contents = fs::read(&archive).unwrap();
+        fs::write(&archive, &contents[..contents.len() / 2]).unwrap();
+
+        let extract_result = testutils::extract_archive(&archive, ArchiveFormat::Zip, &into, &test_env.path());
+        assert!(matches!(extract_result, Err(CacheError::Zip { .. })), "{:?}", extract_result.err());
+        assert_eq!(extract_result.unwrap_err().exit_code(), 6);
+        assert!(matches!(super::read_data_file(&archive), Err(CacheError::Zip { .. })));
+        assert!(!into.exists());
+    }
+
+    #[test]
+    fn corrupted_entries_remove_the_partial_output() {
+        let test_env = TestEnv::new();
+        let archive = test_env.path().join("cache.zip");
+        let into = test_env.path().join("extracted");
+        let contents = testutils::random_text(64 * 1024);
+        testutils::write_zip(&archive, &[Entry::File("deps/a.txt", "a"), Entry::File("deps/b.txt", &contents)]);
+
+        // Changes a byte in the middle of the compressed data of deps/b.txt, which is extracted after deps/a.txt.
+        let mut archive_contents = fs::read(&archive).unwrap();
+        let name_position = archive_contents.windows(10).position(|window| window == b"deps/b.txt").unwrap();
+        archive_contents[name_position + 10 + contents.len() / 4] ^= 0xff;
+        fs::write(&archive, archive_contents).unwrap();
+
+        let extract_result = testutils::extract_archive(&archive, ArchiveFormat::Zip, &into, &test_env.path());
+        assert!(matches!(extract_result, Err(CacheError::Archive { .. })), "{:?}", extract_result.err());
+        assert_eq!(extract_result.unwrap_err().exit_code(), 6);
+        assert!(!into.exists());
+    }
+
+    #[test]
+    fn partial_output_is_removed_inside_of_existing_folders() {
+        let test_env = TestEnv::new();
+        let archive = test_env.path().join("cache.zip");
+        let into = test_env.path().join("extracted");
+        test_env.write("extracted/deps/existing.txt", "existing");
+        let contents = testutils::random_text(64 * 1024);
+        testutils::write_zip(&archive, &[Entry::File("deps/a.txt", "a"), Entry::File("other/b.txt", &contents)]);
+
+        let mut archive_contents = fs::read(&archive).unwrap();
+        let name_position = archive_contents.windows(11).position(|window| window == b"other/b.txt").unwrap();
+        archive_contents[name_position + 11 + contents.len() / 4] ^= 0xff;
+        fs::write(&archive, archive_contents).unwrap();
+
+        assert!(testutils::extract_archive(&archive, ArchiveFormat::Zip, &into, &test_env.path()).is_err());
+        assert_eq!(testutils::read(&into.join("deps/existing.txt")), "existing");
+        assert!(!into.join("deps/a.txt").exists());
+        assert!(!into.join("other").exists());
+    }
+}